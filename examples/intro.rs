@@ -10,61 +10,57 @@
 
 use bevy::input::ButtonState;
 use bevy::{input::keyboard::KeyboardInput, prelude::*};
-use dojo_types::schema::Struct;
-use starknet::core::types::Call;
 use starknet::core::types::Felt;
-use starknet::macros::selector;
-use std::collections::HashSet;
 use torii_grpc_client::types::{Pagination, PaginationDirection, Query as ToriiQuery};
 
 use dojo_bevy_plugin::{
-    DojoEntityUpdated, DojoInitializedEvent, DojoPlugin, DojoResource, TokioRuntime,
+    AccountSource, DojoAppExt, DojoEntityId, DojoEntityMap, DojoManifest, DojoModel,
+    DojoModelUpdated, DojoPlugin, DojoResource, DojoSet, DojoType, TokioRuntime, ToriiState,
 };
 
 const TORII_URL: &str = "http://localhost:8080";
 const KATANA_URL: &str = "http://0.0.0.0:5050";
 
-// Manifest related constants.
-// This is hard coded for now, but we should implement a logic to load
-// those data from the Dojo manifest.
-const WORLD_ADDRESS: Felt =
-    Felt::from_hex_unchecked("0x07cb61df9ec4bdd30ca1f195bc20ff3c7afd0e45e3a3f156767fe05129fd499b");
-const ACTION_ADDRESS: Felt =
-    Felt::from_hex_unchecked("0x0693bc04141539bb8608db41662f7512b57e116087c1c7a529eca0ed4c774ad5");
-const SPAWN_SELECTOR: Felt = selector!("spawn");
-const MOVE_SELECTOR: Felt = selector!("move");
-
-/// This event will be triggered every time the position is updated.
-#[derive(Event)]
-struct PositionUpdatedEvent(pub Position);
+// The manifest generated by `sozo migrate` in the `dojo-intro/contracts` directory.
+// Can be overridden with the `DOJO_MANIFEST` environment variable.
+const MANIFEST_PATH: &str = "../dojo-intro/contracts/manifest_dev.json";
+const ACTIONS_TAG: &str = "di-actions";
 
 /// A very simple cube to represent the player.
 #[derive(Component)]
-pub struct Cube {
-    pub player: Felt,
-}
-
-#[derive(Resource, Default)]
-struct EntityTracker {
-    existing_entities: HashSet<Felt>,
-}
+pub struct Cube;
 
 /// Main entry point.
 fn main() {
+    let manifest_path = std::env::var("DOJO_MANIFEST").unwrap_or(MANIFEST_PATH.to_string());
+    let manifest = DojoManifest::from_path(&manifest_path).expect("Failed to load Dojo manifest");
+
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_plugins(DojoPlugin)
-        .init_resource::<DojoResource>()
-        .init_resource::<TokioRuntime>()
-        .init_resource::<EntityTracker>()
-        .add_event::<PositionUpdatedEvent>()
+        // Dojo connect uses the dojo system to check for async tasks
+        // that initializes connections to Torii and Starknet account.
+        // The `ToriiState` and `StarknetState` states will transition to
+        // `Connected` once the connections are established.
+        .add_plugins(
+            DojoPlugin {
+                torii_url: TORII_URL.to_string(),
+                rpc_url: KATANA_URL.to_string(),
+                account: Some(AccountSource::Predeployed(0)),
+                auto_connect: true,
+                ..Default::default()
+            }
+            .with_manifest(manifest),
+        )
+        // The `Position` component is inserted and updated by the Dojo plugin
+        // on the entity of every Dojo entity with a `di-Position` model.
+        .register_dojo_model::<Position>()
         .add_systems(Startup, setup)
+        .add_systems(OnEnter(ToriiState::Connected), fetch_entities)
         .add_systems(
             Update,
             (
                 handle_keyboard_input,
-                on_dojo_events,
-                (update_cube_position).after(on_dojo_events),
+                (spawn_cubes, update_cube_position).after(DojoSet::SyncModels),
             ),
         )
         .run();
@@ -80,188 +76,122 @@ fn handle_keyboard_input(
         let key_code = event.key_code;
         let is_pressed = event.state == ButtonState::Pressed;
 
-        match key_code {
-            KeyCode::KeyC if is_pressed => {
-                // Dojo connect uses the dojo system to check for async tasks
-                // that initializes connections to Torii and Starknet account.
-                dojo.connect_torii(&tokio, TORII_URL.to_string(), WORLD_ADDRESS);
-                dojo.connect_predeployed_account(&tokio, KATANA_URL.to_string(), 0);
-
-                // Hence, when here, we are not yet connected, until the next
-                // frame.
-                // TODO: this could be improved or by using a `is_ready()` function.
-            }
+        let result = match key_code {
             KeyCode::Space if is_pressed => {
                 info!("Spawning.");
-                let calls = vec![Call {
-                    to: ACTION_ADDRESS,
-                    selector: SPAWN_SELECTOR,
-                    calldata: vec![],
-                }];
-                dojo.queue_tx(&tokio, calls);
+                dojo.call_system(&tokio, ACTIONS_TAG, "spawn", ())
             }
             KeyCode::KeyS if is_pressed => {
                 info!("Setting up Torii subscription.");
                 dojo.subscribe_entities(&tokio, "position".to_string(), None);
+                continue;
             }
             KeyCode::ArrowLeft | KeyCode::ArrowRight | KeyCode::ArrowUp | KeyCode::ArrowDown
                 if is_pressed =>
             {
                 let direction = match key_code {
-                    KeyCode::ArrowLeft => 0,
-                    KeyCode::ArrowRight => 1,
-                    KeyCode::ArrowUp => 2,
-                    KeyCode::ArrowDown => 3,
+                    KeyCode::ArrowLeft => Direction::Left,
+                    KeyCode::ArrowRight => Direction::Right,
+                    KeyCode::ArrowUp => Direction::Up,
+                    KeyCode::ArrowDown => Direction::Down,
                     _ => panic!("Invalid key code"),
                 };
 
-                let calls = vec![Call {
-                    to: ACTION_ADDRESS,
-                    selector: MOVE_SELECTOR,
-                    calldata: vec![Felt::from(direction)],
-                }];
-
-                dojo.queue_tx(&tokio, calls);
+                dojo.call_system(&tokio, ACTIONS_TAG, "move", (direction,))
             }
             _ => continue,
+        };
+
+        if let Err(e) = result {
+            error!("Failed to call the actions contract: {}", e);
         }
     }
 }
 
-/// Updates the cube position by reacting to the dedicated event
-/// for new position updates.
-fn update_cube_position(
+/// Adds a cube to the entities that received a position.
+fn spawn_cubes(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut entity_tracker: ResMut<EntityTracker>,
-    mut ev_position_updated: EventReader<PositionUpdatedEvent>,
-    mut query: Query<(&mut Transform, &Cube)>,
+    query: Query<(Entity, &DojoEntityId, &Position), Added<Position>>,
 ) {
-    for ev in ev_position_updated.read() {
-        let Position { x, y, player } = ev.0;
-
-        if !entity_tracker.existing_entities.contains(&player) {
-            commands.spawn((
-                Mesh3d(meshes.add(Cuboid::new(0.5, 0.5, 0.5))),
-                MeshMaterial3d(materials.add(Color::srgb(0.8, 0.2, 0.2))),
-                Cube { player },
-                Transform::from_xyz(x as f32, y as f32, 0.0),
-            ));
-
-            entity_tracker.existing_entities.insert(player);
-        } else {
-            for (mut transform, cube) in query.iter_mut() {
-                if cube.player == player {
-                    transform.translation = Vec3::new(x as f32, y as f32, 0.0);
-                }
-            }
-        }
+    for (entity, entity_id, position) in query.iter() {
+        info!(entity_id = ?entity_id.0, player = ?position.player, "Player spawned");
+
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Cuboid::new(0.5, 0.5, 0.5))),
+            MeshMaterial3d(materials.add(Color::srgb(0.8, 0.2, 0.2))),
+            Cube,
+            Transform::from_xyz(position.x as f32, position.y as f32, 0.0),
+        ));
     }
 }
 
-/// Reacts on Dojo events, which are emitted by the Dojo plugin.
-///
-/// Any `queue_retrieve_entities` or `subscribe_entities` call will trigger
-/// the `DojoEntityUpdated` event.
-fn on_dojo_events(
-    mut dojo: ResMut<DojoResource>,
-    tokio: Res<TokioRuntime>,
-    mut ev_initialized: EventReader<DojoInitializedEvent>,
-    mut ev_retrieve_entities: EventReader<DojoEntityUpdated>,
-    mut ev_position_updated: EventWriter<PositionUpdatedEvent>,
+/// Updates the cube position every time the position is updated by the Dojo plugin.
+fn update_cube_position(
+    entities: Res<DojoEntityMap>,
+    mut ev_position_updated: EventReader<DojoModelUpdated<Position>>,
+    mut query: Query<&mut Transform, With<Cube>>,
 ) {
-    for _ in ev_initialized.read() {
-        info!("Dojo initialized.");
+    for ev in ev_position_updated.read() {
+        let Some(mut transform) = entities
+            .get(ev.entity_id)
+            .and_then(|e| query.get_mut(e).ok())
+        else {
+            continue;
+        };
 
-        // Initial fetch, which will make the Dojo plugin to send
-        // the query Torii, and trigger the `DojoEntityUpdated` event.
-        dojo.queue_retrieve_entities(
-            &tokio,
-            ToriiQuery {
-                clause: None,
-                pagination: Pagination {
-                    limit: 100,
-                    cursor: None,
-                    direction: PaginationDirection::Forward,
-                    order_by: vec![],
-                },
-                no_hashed_keys: false,
-                models: vec![],
-                historical: false,
-            },
-        );
+        let Position { x, y, .. } = ev.value;
+        transform.translation = Vec3::new(x as f32, y as f32, 0.0);
     }
+}
 
-    // Since the deserialization of the models is project specific,
-    // currently the way it is done is by emitting an event for each
-    // models updates we are interested in.
-    // This may become too much for a large number of models though.
-    // Maybe the solution would be to generate a plugin via bindgen,
-    // that registers all of this automatically.
-    for ev in ev_retrieve_entities.read() {
-        info!(entity_id = ?ev.entity_id, "Torii update");
-
-        // Felt::ZERO is being emitted once, when the subcription is initialized.
-        // We don't want to spawn a cube for this.
-        if ev.entity_id == Felt::ZERO {
-            continue;
-        }
-
-        for m in &ev.models {
-            debug!("model: {:?}", &m);
+/// Fetches the existing entities once connected to Torii.
+///
+/// This will make the Dojo plugin to send the query to Torii,
+/// and insert the `Position` component of the existing players.
+fn fetch_entities(mut dojo: ResMut<DojoResource>, tokio: Res<TokioRuntime>) {
+    info!("Dojo initialized.");
+
+    dojo.queue_retrieve_entities(
+        &tokio,
+        ToriiQuery {
+            clause: None,
+            pagination: Pagination {
+                limit: 100,
+                cursor: None,
+                direction: PaginationDirection::Forward,
+                order_by: vec![],
+            },
+            no_hashed_keys: false,
+            models: vec![],
+            historical: false,
+        },
+    );
+}
 
-            match m.name.as_str() {
-                "di-Position" => {
-                    ev_position_updated.write(PositionUpdatedEvent(m.into()));
-                }
-                name if name == "di-Moves".to_string() => {}
-                _ => {
-                    warn!("Model not handled: {:?}", m);
-                }
-            };
-        }
-    }
+/// The direction of a move, serialized as the `Direction` enum of the actions contract.
+#[derive(Debug, Clone, Copy, DojoType)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
 }
 
 /// The position of the player in the game.
-#[derive(Component, Debug)]
+///
+/// The conversion from and to the `di-Position` model received from Torii
+/// is generated by the `DojoModel` derive macro.
+#[derive(Component, Debug, Clone, DojoModel)]
+#[dojo(namespace = "di", name = "Position")]
 pub struct Position {
+    #[dojo(key)]
     pub player: Felt,
     pub x: u32,
     pub y: u32,
 }
 
-/// This implementation shows a manual way to map data from the Position model in Cairo.
-/// Ideally, we want a binding generation to do that for us.
-impl From<&Struct> for Position {
-    fn from(struct_value: &Struct) -> Self {
-        let player = struct_value
-            .get("player")
-            .unwrap()
-            .as_primitive()
-            .unwrap()
-            .as_contract_address()
-            .unwrap();
-        let x = struct_value
-            .get("x")
-            .unwrap()
-            .as_primitive()
-            .unwrap()
-            .as_u32()
-            .unwrap();
-        let y = struct_value
-            .get("y")
-            .unwrap()
-            .as_primitive()
-            .unwrap()
-            .as_u32()
-            .unwrap();
-
-        Position { player, x, y }
-    }
-}
-
 /// Setups the scene with basic light.
 pub fn setup(mut commands: Commands) {
     commands.spawn((
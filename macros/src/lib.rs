@@ -0,0 +1,430 @@
+//! Derive macros of the Dojo Bevy plugin.
+//!
+//! - `#[derive(DojoModel)]` maps a struct to a Dojo model, using the
+//!   `#[dojo(namespace = "...", name = "...")]` container attribute and the
+//!   `#[dojo(key)]` field attribute.
+//! - `#[derive(DojoType)]` maps a nested struct or an enum to a Dojo type.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{
+    Attribute, Data, DataEnum, DeriveInput, Error, Fields, Ident, LitStr, Type, parse_macro_input,
+};
+
+/// Implements `DojoModel` and `DojoType` for a struct, along with the
+/// `TryFrom<&Struct>` and `From<&T> for Struct` conversions.
+#[proc_macro_derive(DojoModel, attributes(dojo))]
+pub fn derive_dojo_model(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_model(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Implements `DojoType` for a struct or an enum.
+#[proc_macro_derive(DojoType, attributes(dojo))]
+pub fn derive_dojo_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_type(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Attributes of the derived type, `#[dojo(namespace = "...", name = "...")]`.
+#[derive(Default)]
+struct ContainerAttrs {
+    namespace: Option<LitStr>,
+    name: Option<LitStr>,
+}
+
+impl ContainerAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = Self::default();
+
+        for attr in attrs.iter().filter(|a| a.path().is_ident("dojo")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("namespace") {
+                    out.namespace = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("name") {
+                    out.name = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("expected `namespace` or `name`"));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(out)
+    }
+}
+
+/// A named field of a struct.
+struct Field {
+    ident: Ident,
+    ty: Type,
+    key: bool,
+}
+
+/// Parses the named fields of a struct, with their `#[dojo(key)]` attribute.
+fn struct_fields(input: &DeriveInput) -> syn::Result<Vec<Field>> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "expected a struct"));
+    };
+
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "expected a struct with named fields",
+        ));
+    };
+
+    fields
+        .named
+        .iter()
+        .map(|f| {
+            let mut key = false;
+
+            for attr in f.attrs.iter().filter(|a| a.path().is_ident("dojo")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("key") {
+                        key = true;
+                        Ok(())
+                    } else {
+                        Err(meta.error("expected `key`"))
+                    }
+                })?;
+            }
+
+            Ok(Field {
+                ident: f.ident.clone().expect("named field"),
+                ty: f.ty.clone(),
+                key,
+            })
+        })
+        .collect()
+}
+
+/// Expression building the `Struct` from `self`, with the given name.
+fn to_struct(name: &str, fields: &[Field]) -> TokenStream2 {
+    let members = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let member = ident.unraw().to_string();
+        let key = f.key;
+
+        quote! {
+            ::dojo_bevy_plugin::dojo_types::schema::Member {
+                name: #member.to_string(),
+                ty: ::dojo_bevy_plugin::DojoType::to_ty(&self.#ident),
+                key: #key,
+            }
+        }
+    });
+
+    quote! {
+        ::dojo_bevy_plugin::dojo_types::schema::Struct {
+            name: #name.to_string(),
+            children: vec![#(#members),*],
+        }
+    }
+}
+
+/// Expression building the schema `Ty` of the struct, with the given name.
+fn struct_schema(name: &str, fields: &[Field]) -> TokenStream2 {
+    let members = fields.iter().map(|f| {
+        let member = f.ident.unraw().to_string();
+        let ty = &f.ty;
+        let key = f.key;
+
+        quote! {
+            ::dojo_bevy_plugin::dojo_types::schema::Member {
+                name: #member.to_string(),
+                ty: <#ty as ::dojo_bevy_plugin::DojoType>::schema(),
+                key: #key,
+            }
+        }
+    });
+
+    quote! {
+        ::dojo_bevy_plugin::dojo_types::schema::Ty::Struct(
+            ::dojo_bevy_plugin::dojo_types::schema::Struct {
+                name: #name.to_string(),
+                children: vec![#(#members),*],
+            }
+        )
+    }
+}
+
+/// Expression building `Self` from the `s: &Struct` variable.
+fn from_struct(fields: &[Field]) -> TokenStream2 {
+    let members = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let member = ident.unraw().to_string();
+
+        quote! {
+            #ident: ::dojo_bevy_plugin::DojoType::from_ty(
+                ::dojo_bevy_plugin::__struct_member(s, #member)?
+            )?
+        }
+    });
+
+    quote! {
+        Self { #(#members),* }
+    }
+}
+
+/// Implements `DojoType` for a struct, identified by the given name.
+fn expand_struct_type(input: &DeriveInput, name: &str, fields: &[Field]) -> TokenStream2 {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let from_struct = from_struct(fields);
+    let to_struct = to_struct(name, fields);
+    let schema = struct_schema(name, fields);
+
+    quote! {
+        impl #impl_generics ::dojo_bevy_plugin::DojoType for #ident #ty_generics #where_clause {
+            fn from_ty(
+                ty: &::dojo_bevy_plugin::dojo_types::schema::Ty,
+            ) -> ::core::result::Result<Self, ::dojo_bevy_plugin::DojoModelError> {
+                match ty {
+                    ::dojo_bevy_plugin::dojo_types::schema::Ty::Struct(s) => Ok(#from_struct),
+                    _ => Err(::dojo_bevy_plugin::DojoModelError::type_mismatch(#name, ty)),
+                }
+            }
+
+            fn to_ty(&self) -> ::dojo_bevy_plugin::dojo_types::schema::Ty {
+                ::dojo_bevy_plugin::dojo_types::schema::Ty::Struct(#to_struct)
+            }
+
+            fn schema() -> ::dojo_bevy_plugin::dojo_types::schema::Ty {
+                #schema
+            }
+        }
+    }
+}
+
+/// Implements `DojoType` for an enum, identified by the given name.
+///
+/// Variants must be unit variants or tuple variants with a single field.
+fn expand_enum_type(input: &DeriveInput, name: &str, data: &DataEnum) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    if data.variants.len() > u8::MAX as usize {
+        return Err(Error::new_spanned(ident, "too many variants"));
+    }
+
+    let mut options = Vec::new();
+    let mut from_arms = Vec::new();
+    let mut to_arms = Vec::new();
+
+    for (idx, variant) in data.variants.iter().enumerate() {
+        let variant_ident = &variant.ident;
+        let variant_name = variant_ident.unraw().to_string();
+        let idx = idx as u8;
+
+        match &variant.fields {
+            Fields::Unit => {
+                options.push(quote! {
+                    ::dojo_bevy_plugin::dojo_types::schema::EnumOption {
+                        name: #variant_name.to_string(),
+                        ty: ::dojo_bevy_plugin::dojo_types::schema::Ty::Tuple(vec![]),
+                    }
+                });
+                from_arms.push(quote! { #variant_name => Ok(Self::#variant_ident) });
+                to_arms.push(quote! {
+                    Self::#variant_ident => (
+                        #idx,
+                        ::dojo_bevy_plugin::dojo_types::schema::Ty::Tuple(vec![]),
+                    )
+                });
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                options.push(quote! {
+                    ::dojo_bevy_plugin::dojo_types::schema::EnumOption {
+                        name: #variant_name.to_string(),
+                        ty: <#ty as ::dojo_bevy_plugin::DojoType>::schema(),
+                    }
+                });
+                from_arms.push(quote! {
+                    #variant_name => Ok(Self::#variant_ident(
+                        ::dojo_bevy_plugin::DojoType::from_ty(&option.ty)?
+                    ))
+                });
+                to_arms.push(quote! {
+                    Self::#variant_ident(v) => (#idx, ::dojo_bevy_plugin::DojoType::to_ty(v))
+                });
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "expected a unit variant or a tuple variant with a single field",
+                ));
+            }
+        }
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::dojo_bevy_plugin::DojoType for #ident #ty_generics #where_clause {
+            fn from_ty(
+                ty: &::dojo_bevy_plugin::dojo_types::schema::Ty,
+            ) -> ::core::result::Result<Self, ::dojo_bevy_plugin::DojoModelError> {
+                let ::dojo_bevy_plugin::dojo_types::schema::Ty::Enum(e) = ty else {
+                    return Err(::dojo_bevy_plugin::DojoModelError::type_mismatch(#name, ty));
+                };
+
+                let option = e
+                    .option
+                    .and_then(|o| e.options.get(o as usize))
+                    .ok_or_else(|| ::dojo_bevy_plugin::DojoModelError::MissingVariant {
+                        ty: #name.to_string(),
+                    })?;
+
+                match option.name.as_str() {
+                    #(#from_arms,)*
+                    variant => Err(::dojo_bevy_plugin::DojoModelError::UnknownVariant {
+                        ty: #name.to_string(),
+                        variant: variant.to_string(),
+                    }),
+                }
+            }
+
+            fn to_ty(&self) -> ::dojo_bevy_plugin::dojo_types::schema::Ty {
+                let (option, ty) = match self {
+                    #(#to_arms,)*
+                };
+
+                let mut options = vec![#(#options),*];
+                options[option as usize].ty = ty;
+
+                ::dojo_bevy_plugin::dojo_types::schema::Ty::Enum(
+                    ::dojo_bevy_plugin::dojo_types::schema::Enum {
+                        name: #name.to_string(),
+                        option: Some(option),
+                        options,
+                    }
+                )
+            }
+
+            fn schema() -> ::dojo_bevy_plugin::dojo_types::schema::Ty {
+                ::dojo_bevy_plugin::dojo_types::schema::Ty::Enum(
+                    ::dojo_bevy_plugin::dojo_types::schema::Enum {
+                        name: #name.to_string(),
+                        option: None,
+                        options: vec![#(#options),*],
+                    }
+                )
+            }
+        }
+    })
+}
+
+fn expand_type(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let attrs = ContainerAttrs::parse(&input.attrs)?;
+    let name = attrs
+        .name
+        .map(|n| n.value())
+        .unwrap_or_else(|| input.ident.unraw().to_string());
+
+    match &input.data {
+        Data::Struct(_) => {
+            let fields = struct_fields(input)?;
+            Ok(expand_struct_type(input, &name, &fields))
+        }
+        Data::Enum(data) => expand_enum_type(input, &name, data),
+        Data::Union(_) => Err(Error::new_spanned(&input.ident, "unions are not supported")),
+    }
+}
+
+fn expand_model(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let attrs = ContainerAttrs::parse(&input.attrs)?;
+
+    let Some(namespace) = attrs.namespace.map(|n| n.value()) else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "missing `#[dojo(namespace = \"...\")]` attribute",
+        ));
+    };
+
+    let name = attrs
+        .name
+        .map(|n| n.value())
+        .unwrap_or_else(|| input.ident.unraw().to_string());
+
+    // Torii names the models by their tag.
+    let tag = format!("{}-{}", namespace, name);
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = struct_fields(input)?;
+    let dojo_type = expand_struct_type(input, &tag, &fields);
+    let from_struct = from_struct(&fields);
+    let to_struct = to_struct(&tag, &fields);
+
+    let keys = fields.iter().filter(|f| f.key).map(|f| {
+        let ident = &f.ident;
+        quote! { keys.extend(::dojo_bevy_plugin::__serialize(&self.#ident)?); }
+    });
+
+    Ok(quote! {
+        #dojo_type
+
+        impl #impl_generics ::dojo_bevy_plugin::DojoModel for #ident #ty_generics #where_clause {
+            const NAMESPACE: &'static str = #namespace;
+            const NAME: &'static str = #name;
+
+            fn from_struct(
+                s: &::dojo_bevy_plugin::dojo_types::schema::Struct,
+            ) -> ::core::result::Result<Self, ::dojo_bevy_plugin::DojoModelError> {
+                if s.name != #tag {
+                    return Err(::dojo_bevy_plugin::DojoModelError::UnexpectedModel {
+                        expected: #tag.to_string(),
+                        found: s.name.clone(),
+                    });
+                }
+
+                Ok(#from_struct)
+            }
+
+            fn to_struct(&self) -> ::dojo_bevy_plugin::dojo_types::schema::Struct {
+                #to_struct
+            }
+
+            fn keys(
+                &self,
+            ) -> ::core::result::Result<
+                ::std::vec::Vec<::dojo_bevy_plugin::starknet::core::types::Felt>,
+                ::dojo_bevy_plugin::DojoModelError,
+            > {
+                let mut keys = ::std::vec::Vec::new();
+                #(#keys)*
+                Ok(keys)
+            }
+        }
+
+        impl #impl_generics ::core::convert::TryFrom<&::dojo_bevy_plugin::dojo_types::schema::Struct>
+            for #ident #ty_generics #where_clause
+        {
+            type Error = ::dojo_bevy_plugin::DojoModelError;
+
+            fn try_from(
+                s: &::dojo_bevy_plugin::dojo_types::schema::Struct,
+            ) -> ::core::result::Result<Self, Self::Error> {
+                <Self as ::dojo_bevy_plugin::DojoModel>::from_struct(s)
+            }
+        }
+
+        impl #impl_generics ::core::convert::From<&#ident #ty_generics>
+            for ::dojo_bevy_plugin::dojo_types::schema::Struct #where_clause
+        {
+            fn from(value: &#ident #ty_generics) -> Self {
+                ::dojo_bevy_plugin::DojoModel::to_struct(value)
+            }
+        }
+    })
+}
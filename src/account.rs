@@ -0,0 +1,693 @@
+//! Starknet accounts.
+//!
+//! A [`DojoAccount`] is either an account controlled by its signer, or a
+//! session of a player account. With a session, the player approves once a set of
+//! policies (the contracts and selectors allowed) until an expiry, and the game signs
+//! the moves with an ephemeral session key.
+//!
+//! Sessions follow the format of the Argent account (the `session` module of
+//! argent-contracts-starknet 0.4). The owner of the account signs the SNIP-12 hash of
+//! the session, made of its expiry, the merkle root of the allowed methods, the hash of
+//! its metadata and the session key. The calls are then wrapped into a SNIP-9 outside
+//! execution, whose signature is a session token signed by the session key and the
+//! guardian of the account, along with the merkle proofs of the calls.
+//!
+//! The outside executions are sent by another account paying the fees (a relayer,
+//! or a burner account of the game).
+
+use starknet::accounts::{Account, ConnectedAccount, SingleOwnerAccount};
+use starknet::core::crypto::Signature;
+use starknet::core::types::{Call, Felt};
+use starknet::core::utils::{get_selector_from_name, starknet_keccak};
+use starknet::providers::AnyProvider;
+use starknet::signers::SigningKey;
+use starknet_crypto::{poseidon_hash, poseidon_hash_many};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::DojoError;
+use crate::manifest::DojoManifest;
+use crate::plugin::AccountSource;
+use crate::signer::DojoWallet;
+
+/// SNIP-12 (revision 1) type hash of the `StarknetDomain`.
+const STARKNET_DOMAIN_TYPE_HASH: Felt =
+    Felt::from_hex_unchecked("0x1ff2f602e42168014d405a94f75e8a93d640751d71d16311266e140d8b0a210");
+
+/// SNIP-12 (revision 1) type hash of the SNIP-9 `OutsideExecution`.
+const OUTSIDE_EXECUTION_TYPE_HASH: Felt =
+    Felt::from_hex_unchecked("0x312b56c05a7965066ddbda31c016d8d05afc305071c0ca3cdc2192c3c2f1f0f");
+
+/// SNIP-12 (revision 1) type hash of the `Call` of an outside execution.
+const CALL_TYPE_HASH: Felt =
+    Felt::from_hex_unchecked("0x3635c7f2a7ba93844c0d064e18e487f35ab90f7c39d00f186a781fc3f0c2ca9");
+
+/// SNIP-12 (revision 1) type hash of the `Session` of the Argent account.
+const SESSION_TYPE_HASH: Felt =
+    Felt::from_hex_unchecked("0x2a7d1ecdf754b100d735189f4969485656c828bfcb863a154c61199caa02434");
+
+/// SNIP-12 (revision 1) type hash of the `Allowed Method` leaves of a session.
+const ALLOWED_METHOD_TYPE_HASH: Felt =
+    Felt::from_hex_unchecked("0x38bb0eaaded40ffd0ffd2995e2b7603ee76746158c2f7cd494f201d4ca16a86");
+
+/// The account used to send the transactions.
+pub enum DojoAccount {
+    /// An account controlled by its signer.
+    SingleOwner(SingleOwnerAccount<AnyProvider, DojoWallet>),
+    /// A session of a player account, executed through SNIP-9 outside execution.
+    Session(SessionAccount),
+}
+
+impl DojoAccount {
+    /// Returns the address of the account, the player account for a session.
+    pub fn address(&self) -> Felt {
+        match self {
+            Self::SingleOwner(account) => account.address(),
+            Self::Session(session) => session.address,
+        }
+    }
+
+    /// Returns the account sending the transactions and paying the fees.
+    pub fn sender(&self) -> &SingleOwnerAccount<AnyProvider, DojoWallet> {
+        match self {
+            Self::SingleOwner(account) => account,
+            Self::Session(session) => &session.sender,
+        }
+    }
+
+    /// Returns the provider of the account.
+    pub fn provider(&self) -> &AnyProvider {
+        self.sender().provider()
+    }
+
+    /// Returns the calls to send with the `sender` account to execute the given calls.
+    ///
+    /// The calls of a session are checked against its policies and wrapped
+    /// into a single outside execution, co-signed by the guardian of the account.
+    pub async fn sender_calls(&self, calls: Vec<Call>) -> Result<Vec<Call>, DojoError> {
+        match self {
+            Self::SingleOwner(_) => Ok(calls),
+            Self::Session(session) => Ok(vec![session.outside_execution(calls).await?]),
+        }
+    }
+}
+
+/// A contract and a selector a session is allowed to call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionPolicy {
+    pub contract_address: Felt,
+    pub selector: Felt,
+}
+
+impl SessionPolicy {
+    /// Builds the policy allowing a system of a contract registered in the world,
+    /// with the address of the contract resolved from the manifest.
+    pub fn system(
+        manifest: &DojoManifest,
+        contract_tag: &str,
+        system: &str,
+    ) -> Result<Self, DojoError> {
+        let not_found = || DojoError::SystemNotFound {
+            contract: contract_tag.to_string(),
+            system: system.to_string(),
+        };
+
+        let contract = manifest
+            .contract(contract_tag)
+            .ok_or_else(|| DojoError::ContractNotFound(contract_tag.to_string()))?;
+
+        if contract.abi_system(system).is_none() && !contract.has_system(system) {
+            return Err(not_found());
+        }
+
+        Ok(Self {
+            contract_address: contract.address,
+            selector: get_selector_from_name(system).map_err(|_| not_found())?,
+        })
+    }
+
+    /// Returns the leaf of the policy in the merkle tree of the allowed methods.
+    fn leaf(&self) -> Felt {
+        poseidon_hash_many(&[
+            ALLOWED_METHOD_TYPE_HASH,
+            self.contract_address,
+            self.selector,
+        ])
+    }
+}
+
+/// A session approved by the player, allowing the session key to execute
+/// the calls matching its policies until it expires.
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// The allowed methods, in the order of the leaves of their merkle tree.
+    pub policies: Vec<SessionPolicy>,
+    /// Expiry of the session, as a UNIX timestamp in seconds.
+    pub expires_at: u64,
+    /// The hash of the metadata of the session, as displayed to the player.
+    pub metadata_hash: Felt,
+}
+
+impl Session {
+    /// Returns true if the call matches one of the policies.
+    pub fn allows(&self, call: &Call) -> bool {
+        self.policies
+            .iter()
+            .any(|p| p.contract_address == call.to && p.selector == call.selector)
+    }
+
+    /// Returns true if the session has expired.
+    pub fn is_expired(&self) -> bool {
+        unix_now() >= self.expires_at
+    }
+
+    /// Returns the merkle root of the allowed methods.
+    pub fn allowed_methods_root(&self) -> Felt {
+        MerkleTree::new(&self.policies).root()
+    }
+
+    /// Returns the SNIP-12 (revision 1) hash of the session, to be signed by the
+    /// owner of the account to approve the session key, on the given chain.
+    pub fn hash(&self, chain_id: Felt, account: Felt, session_public_key: Felt) -> Felt {
+        let domain = poseidon_hash_many(&[
+            STARKNET_DOMAIN_TYPE_HASH,
+            Felt::from_bytes_be_slice(b"SessionAccount.session"),
+            Felt::from_bytes_be_slice(b"1"),
+            chain_id,
+            Felt::ONE,
+        ]);
+
+        let session = poseidon_hash_many(&[
+            SESSION_TYPE_HASH,
+            self.expires_at.into(),
+            self.allowed_methods_root(),
+            self.metadata_hash,
+            starknet_signer_guid(session_public_key),
+        ]);
+
+        poseidon_hash_many(&[
+            Felt::from_bytes_be_slice(b"StarkNet Message"),
+            domain,
+            account,
+            session,
+        ])
+    }
+}
+
+/// Configuration of the session of a player account.
+#[derive(Clone)]
+pub struct SessionConfig {
+    /// The player account, an Argent account supporting SNIP-9 outside execution.
+    pub address: Felt,
+    pub session: Session,
+    /// The private key of the session.
+    pub session_key: Felt,
+    /// The signature of the session hash (see `Session::hash`) by the owner
+    /// of the account, in the signature format of the account.
+    pub authorization: Vec<Felt>,
+    /// The guardian of the account, co-signing every outside execution of the session.
+    pub guardian: DojoWallet,
+    /// The account sending the outside executions and paying the fees,
+    /// which can't be a session itself.
+    pub sender: Box<AccountSource>,
+}
+
+/// A session of a player account, sending its calls through another account.
+pub struct SessionAccount {
+    sender: SingleOwnerAccount<AnyProvider, DojoWallet>,
+    address: Felt,
+    session: Session,
+    session_key: SigningKey,
+    authorization: Vec<Felt>,
+    guardian: DojoWallet,
+    next_nonce: AtomicU64,
+}
+
+impl SessionAccount {
+    /// Creates the session account, with the calls sent by `sender`.
+    pub fn new(sender: SingleOwnerAccount<AnyProvider, DojoWallet>, config: SessionConfig) -> Self {
+        // Outside execution nonces only have to be unique, starting from
+        // the current time keeps them unique across restarts.
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+
+        Self {
+            sender,
+            address: config.address,
+            session: config.session,
+            session_key: SigningKey::from_secret_scalar(config.session_key),
+            authorization: config.authorization,
+            guardian: config.guardian,
+            next_nonce: AtomicU64::new(nanos),
+        }
+    }
+
+    /// Returns the session of the account.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Wraps the calls into an outside execution of the player account,
+    /// signed by the session key and the guardian.
+    ///
+    /// The signature is the `session-token` magic value followed by the serialized
+    /// `SessionToken` of the Argent account: the session, the authorization of the
+    /// owner, the signatures of the session key and of the guardian, and the merkle
+    /// proofs of the calls.
+    pub async fn outside_execution(&self, calls: Vec<Call>) -> Result<Call, DojoError> {
+        if self.session.is_expired() {
+            return Err(DojoError::SessionExpired(self.session.expires_at));
+        }
+
+        let tree = MerkleTree::new(&self.session.policies);
+        let proofs = calls
+            .iter()
+            .map(|call| {
+                tree.proof(call).ok_or(DojoError::SessionPolicy {
+                    contract_address: call.to,
+                    selector: call.selector,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let execution = OutsideExecution {
+            caller: self.sender.address(),
+            nonce: self.next_nonce.fetch_add(1, Ordering::Relaxed).into(),
+            execute_after: 0,
+            execute_before: self.session.expires_at,
+            calls,
+        };
+
+        let chain_id = self.sender.chain_id();
+        let session_public_key = self.session_key.verifying_key().scalar();
+        let session_hash = self
+            .session
+            .hash(chain_id, self.address, session_public_key);
+
+        // The authorization is not cached by the account, it's checked with every execution.
+        let cache_authorization = Felt::ZERO;
+        let hash = poseidon_hash_many(&[
+            execution.message_hash(chain_id, self.address),
+            session_hash,
+            cache_authorization,
+        ]);
+
+        let session_signature = self
+            .session_key
+            .sign(&hash)
+            .map_err(|e| DojoError::Signing(e.to_string()))?;
+
+        let guardian_public_key = self
+            .guardian
+            .0
+            .get_public_key()
+            .await
+            .map_err(|e| DojoError::Signing(e.to_string()))?
+            .scalar();
+        let guardian_signature = self
+            .guardian
+            .0
+            .sign_hash(&hash)
+            .await
+            .map_err(|e| DojoError::Signing(e.to_string()))?;
+
+        let mut signature = vec![
+            Felt::from_bytes_be_slice(b"session-token"),
+            self.session.expires_at.into(),
+            tree.root(),
+            self.session.metadata_hash,
+            starknet_signer_guid(session_public_key),
+            cache_authorization,
+            self.authorization.len().into(),
+        ];
+        signature.extend(self.authorization.iter().copied());
+        signature.extend(starknet_signer_signature(
+            session_public_key,
+            &session_signature,
+        ));
+        signature.extend(starknet_signer_signature(
+            guardian_public_key,
+            &guardian_signature,
+        ));
+        signature.push(proofs.len().into());
+        for proof in proofs {
+            signature.push(proof.len().into());
+            signature.extend(proof);
+        }
+
+        let mut calldata = execution.calldata();
+        calldata.push(signature.len().into());
+        calldata.extend(signature);
+
+        Ok(Call {
+            to: self.address,
+            selector: starknet_keccak(b"execute_from_outside_v2"),
+            calldata,
+        })
+    }
+}
+
+/// The merkle tree of the allowed methods of a session, built as the
+/// `MerkleTree` of starknet.js: the pairs are sorted before being hashed,
+/// and the last node of a level with an odd length is paired with zero.
+struct MerkleTree {
+    /// The levels of the tree, from the leaves to the root.
+    levels: Vec<Vec<Felt>>,
+}
+
+impl MerkleTree {
+    fn new(policies: &[SessionPolicy]) -> Self {
+        let mut level: Vec<Felt> = policies.iter().map(SessionPolicy::leaf).collect();
+        let mut levels = Vec::new();
+
+        while level.len() > 1 {
+            let next = level
+                .chunks(2)
+                .map(|pair| merkle_hash(pair[0], pair.get(1).copied().unwrap_or(Felt::ZERO)))
+                .collect();
+            levels.push(std::mem::replace(&mut level, next));
+        }
+
+        levels.push(level);
+        Self { levels }
+    }
+
+    /// Returns the root of the tree, zero if there is no policy.
+    fn root(&self) -> Felt {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or(Felt::ZERO)
+    }
+
+    /// Returns the proof of the policy of the call, if it is allowed.
+    fn proof(&self, call: &Call) -> Option<Vec<Felt>> {
+        let policy = SessionPolicy {
+            contract_address: call.to,
+            selector: call.selector,
+        };
+        let mut index = self.levels[0].iter().position(|l| *l == policy.leaf())?;
+
+        let proof = self.levels[..self.levels.len() - 1]
+            .iter()
+            .map(|level| {
+                let sibling = level.get(index ^ 1).copied().unwrap_or(Felt::ZERO);
+                index /= 2;
+                sibling
+            })
+            .collect();
+
+        Some(proof)
+    }
+}
+
+/// Hashes two nodes of the merkle tree, in ascending order.
+fn merkle_hash(a: Felt, b: Felt) -> Felt {
+    if a < b {
+        poseidon_hash(a, b)
+    } else {
+        poseidon_hash(b, a)
+    }
+}
+
+/// Returns the GUID of the Starknet signer with the given public key.
+fn starknet_signer_guid(public_key: Felt) -> Felt {
+    poseidon_hash_many(&[Felt::from_bytes_be_slice(b"Starknet Signer"), public_key])
+}
+
+/// Returns the serialization of a `SignerSignature::Starknet`.
+fn starknet_signer_signature(public_key: Felt, signature: &Signature) -> [Felt; 4] {
+    [Felt::ZERO, public_key, signature.r, signature.s]
+}
+
+/// A SNIP-9 (version 2) outside execution.
+struct OutsideExecution {
+    caller: Felt,
+    nonce: Felt,
+    execute_after: u64,
+    execute_before: u64,
+    calls: Vec<Call>,
+}
+
+impl OutsideExecution {
+    /// Returns the SNIP-12 (revision 1) hash of the outside execution, to be signed.
+    fn message_hash(&self, chain_id: Felt, account: Felt) -> Felt {
+        let domain = poseidon_hash_many(&[
+            STARKNET_DOMAIN_TYPE_HASH,
+            Felt::from_bytes_be_slice(b"Account.execute_from_outside"),
+            Felt::TWO,
+            chain_id,
+            Felt::ONE,
+        ]);
+
+        let calls: Vec<Felt> = self
+            .calls
+            .iter()
+            .map(|c| {
+                poseidon_hash_many(&[
+                    CALL_TYPE_HASH,
+                    c.to,
+                    c.selector,
+                    poseidon_hash_many(&c.calldata),
+                ])
+            })
+            .collect();
+
+        let execution = poseidon_hash_many(&[
+            OUTSIDE_EXECUTION_TYPE_HASH,
+            self.caller,
+            self.nonce,
+            self.execute_after.into(),
+            self.execute_before.into(),
+            poseidon_hash_many(&calls),
+        ]);
+
+        poseidon_hash_many(&[
+            Felt::from_bytes_be_slice(b"StarkNet Message"),
+            domain,
+            account,
+            execution,
+        ])
+    }
+
+    /// Returns the Cairo serialization of the outside execution.
+    fn calldata(&self) -> Vec<Felt> {
+        let mut calldata = vec![
+            self.caller,
+            self.nonce,
+            self.execute_after.into(),
+            self.execute_before.into(),
+            self.calls.len().into(),
+        ];
+
+        for call in &self.calls {
+            calldata.extend([call.to, call.selector, call.calldata.len().into()]);
+            calldata.extend(call.calldata.iter().copied());
+        }
+
+        calldata
+    }
+}
+
+/// Returns the current UNIX timestamp, in seconds.
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// `SN_SEPOLIA`.
+    const CHAIN_ID: Felt = Felt::from_hex_unchecked("0x534e5f5345504f4c4941");
+    const ACCOUNT: Felt = Felt::from_hex_unchecked("0x1234");
+
+    fn policy(contract_address: u64, system: &str) -> SessionPolicy {
+        SessionPolicy {
+            contract_address: contract_address.into(),
+            selector: get_selector_from_name(system).unwrap(),
+        }
+    }
+
+    fn call(policy: SessionPolicy) -> Call {
+        Call {
+            to: policy.contract_address,
+            selector: policy.selector,
+            calldata: vec![],
+        }
+    }
+
+    fn session() -> Session {
+        Session {
+            policies: vec![
+                policy(0x5678, "move"),
+                policy(0x5678, "spawn"),
+                policy(0x9abc, "move"),
+            ],
+            expires_at: 1_700_000_000,
+            metadata_hash: Felt::from(0x77),
+        }
+    }
+
+    #[test]
+    fn type_hashes() {
+        // Published by SNIP-9 (outside execution V2), and by the `snip12`
+        // modules of the OpenZeppelin Cairo contracts.
+        let published = [
+            (
+                STARKNET_DOMAIN_TYPE_HASH,
+                "0x1ff2f602e42168014d405a94f75e8a93d640751d71d16311266e140d8b0a210",
+            ),
+            (
+                OUTSIDE_EXECUTION_TYPE_HASH,
+                "0x312b56c05a7965066ddbda31c016d8d05afc305071c0ca3cdc2192c3c2f1f0f",
+            ),
+            (
+                CALL_TYPE_HASH,
+                "0x3635c7f2a7ba93844c0d064e18e487f35ab90f7c39d00f186a781fc3f0c2ca9",
+            ),
+        ];
+
+        for (type_hash, expected) in published {
+            assert_eq!(type_hash, Felt::from_hex_unchecked(expected));
+        }
+
+        // The session type hashes are checked against the types of the Argent session.
+        let type_hashes = [
+            (
+                STARKNET_DOMAIN_TYPE_HASH,
+                r#""StarknetDomain"("name":"shortstring","version":"shortstring","chainId":"shortstring","revision":"shortstring")"#,
+            ),
+            (
+                OUTSIDE_EXECUTION_TYPE_HASH,
+                r#""OutsideExecution"("Caller":"ContractAddress","Nonce":"felt","Execute After":"u128","Execute Before":"u128","Calls":"Call*")"Call"("To":"ContractAddress","Selector":"selector","Calldata":"felt*")"#,
+            ),
+            (
+                CALL_TYPE_HASH,
+                r#""Call"("To":"ContractAddress","Selector":"selector","Calldata":"felt*")"#,
+            ),
+            (
+                SESSION_TYPE_HASH,
+                r#""Session"("Expires At":"timestamp","Allowed Methods":"merkletree","Metadata":"string","Session Key":"felt")"#,
+            ),
+            (
+                ALLOWED_METHOD_TYPE_HASH,
+                r#""Allowed Method"("Contract Address":"ContractAddress","selector":"selector")"#,
+            ),
+        ];
+
+        for (type_hash, encoded_type) in type_hashes {
+            assert_eq!(
+                type_hash,
+                starknet_keccak(encoded_type.as_bytes()),
+                "{encoded_type}"
+            );
+        }
+    }
+
+    fn execution() -> OutsideExecution {
+        OutsideExecution {
+            caller: Felt::from_bytes_be_slice(b"ANY_CALLER"),
+            nonce: Felt::from(7),
+            execute_after: 0,
+            execute_before: 1_700_000_000,
+            calls: vec![Call {
+                calldata: vec![Felt::ONE, Felt::TWO],
+                ..call(policy(0x5678, "move"))
+            }],
+        }
+    }
+
+    #[test]
+    fn outside_execution_message_hash() {
+        let mut other_calldata = execution();
+        other_calldata.calls[0].calldata.reverse();
+
+        // The signature can't be replayed on another chain, account or execution.
+        let hashes: HashSet<Felt> = [
+            execution().message_hash(CHAIN_ID, ACCOUNT),
+            execution().message_hash(Felt::from_bytes_be_slice(b"SN_MAIN"), ACCOUNT),
+            execution().message_hash(CHAIN_ID, Felt::from(0x4321)),
+            OutsideExecution {
+                nonce: Felt::from(8),
+                ..execution()
+            }
+            .message_hash(CHAIN_ID, ACCOUNT),
+            OutsideExecution {
+                execute_before: 1_700_000_001,
+                ..execution()
+            }
+            .message_hash(CHAIN_ID, ACCOUNT),
+            other_calldata.message_hash(CHAIN_ID, ACCOUNT),
+        ]
+        .into();
+
+        assert_eq!(hashes.len(), 6);
+    }
+
+    #[test]
+    fn session_hash() {
+        let mut other_policies = session();
+        other_policies.policies.pop();
+
+        let roots: HashSet<Felt> = [
+            session().allowed_methods_root(),
+            other_policies.allowed_methods_root(),
+        ]
+        .into();
+        assert_eq!(roots.len(), 2);
+
+        // The approval can't be replayed on another chain, account, session key or session.
+        let hashes: HashSet<Felt> = [
+            session().hash(CHAIN_ID, ACCOUNT, Felt::TWO),
+            session().hash(Felt::from_bytes_be_slice(b"SN_MAIN"), ACCOUNT, Felt::TWO),
+            session().hash(CHAIN_ID, Felt::from(0x4321), Felt::TWO),
+            session().hash(CHAIN_ID, ACCOUNT, Felt::THREE),
+            other_policies.hash(CHAIN_ID, ACCOUNT, Felt::TWO),
+            Session {
+                expires_at: 1_700_000_001,
+                ..session()
+            }
+            .hash(CHAIN_ID, ACCOUNT, Felt::TWO),
+        ]
+        .into();
+
+        assert_eq!(hashes.len(), 6);
+    }
+
+    #[test]
+    fn merkle_proofs() {
+        let session = session();
+        let tree = MerkleTree::new(&session.policies);
+
+        for policy in &session.policies {
+            let proof = tree.proof(&call(*policy)).unwrap();
+            let root = proof
+                .iter()
+                .fold(policy.leaf(), |node, p| merkle_hash(node, *p));
+            assert_eq!(root, tree.root());
+        }
+
+        // The last leaf is paired with zero.
+        let proof = tree.proof(&call(policy(0x9abc, "move"))).unwrap();
+        assert_eq!(proof[0], Felt::ZERO);
+
+        assert_eq!(tree.proof(&call(policy(0x9abc, "spawn"))), None);
+    }
+
+    #[test]
+    fn merkle_single_policy() {
+        let policy = policy(0x5678, "move");
+        let tree = MerkleTree::new(&[policy]);
+
+        assert_eq!(tree.root(), policy.leaf());
+        assert_eq!(tree.proof(&call(policy)), Some(vec![]));
+    }
+}
@@ -0,0 +1,35 @@
+//! Generates the Rust bindings of a Dojo world from its manifest.
+//!
+//! Usage: `dojo-bevy-bindgen <manifest_path> [output_path]`
+//!
+//! The bindings are written to the standard output if no output path is given.
+
+use dojo_bevy_plugin::{DojoManifest, generate_bindings};
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let (manifest_path, output_path) = match args.as_slice() {
+        [manifest] => (manifest, None),
+        [manifest, output] => (manifest, Some(output)),
+        _ => {
+            eprintln!("Usage: dojo-bevy-bindgen <manifest_path> [output_path]");
+            std::process::exit(2);
+        }
+    };
+
+    let bindings = DojoManifest::from_path(manifest_path).and_then(|m| generate_bindings(&m));
+
+    let result = bindings.and_then(|bindings| match output_path {
+        Some(path) => std::fs::write(path, bindings).map_err(Into::into),
+        None => {
+            print!("{}", bindings);
+            Ok(())
+        }
+    });
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,596 @@
+//! Rust bindings generation from the Dojo manifest.
+//!
+//! The bindings contain a component for every model of the world, the structs
+//! and enums they use (resolved from the contracts ABI), the helpers building
+//! the `DojoSystem` of every system of the contracts, and a `DojoBindingsPlugin`
+//! registering all the models.
+//!
+//! The structs and enums are generated under their name, without their Cairo path:
+//! two types with the same name, and generic types, are not supported.
+//!
+//! The bindings are usually generated from a build script into `OUT_DIR`:
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     let out_dir = std::env::var("OUT_DIR").unwrap();
+//!     dojo_bevy_plugin::write_bindings(
+//!         "contracts/manifest_dev.json",
+//!         std::path::Path::new(&out_dir).join("dojo_bindings.rs"),
+//!     )
+//!     .unwrap();
+//!     println!("cargo:rerun-if-changed=contracts/manifest_dev.json");
+//! }
+//!
+//! // src/main.rs
+//! #[allow(dead_code)]
+//! mod bindings {
+//!     include!(concat!(env!("OUT_DIR"), "/dojo_bindings.rs"));
+//! }
+//! ```
+//!
+//! The `dojo-bevy-bindgen` binary writes the same bindings to a file or to the standard output.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::error::DojoError;
+use crate::manifest::{DojoManifest, ManifestContract, ManifestMember, abi_fields};
+
+/// Generates the bindings of the world described by the manifest file, and writes them to `out`.
+pub fn write_bindings(manifest: impl AsRef<Path>, out: impl AsRef<Path>) -> Result<(), DojoError> {
+    let manifest = DojoManifest::from_path(manifest)?;
+    std::fs::write(out, generate_bindings(&manifest)?)?;
+    Ok(())
+}
+
+/// Generates the bindings of the world described by the manifest.
+pub fn generate_bindings(manifest: &DojoManifest) -> Result<String, DojoError> {
+    Bindgen::new(manifest).generate()
+}
+
+/// A struct or an enum declared in the ABI of a contract.
+pub(crate) enum AbiType {
+    Struct(Vec<(String, String)>),
+    Enum(Vec<(String, String)>),
+}
+
+/// A Cairo type, as written in the manifest and the ABIs.
+#[derive(Debug, PartialEq)]
+pub(crate) enum CairoType {
+    Path { path: String, args: Vec<CairoType> },
+    Tuple(Vec<CairoType>),
+}
+
+impl CairoType {
+    pub(crate) fn parse(s: &str) -> Result<Self, DojoError> {
+        let s = s.trim();
+
+        if let Some(inner) = s.strip_prefix('(') {
+            let inner = inner
+                .strip_suffix(')')
+                .ok_or_else(|| DojoError::Bindgen(format!("Invalid tuple type `{}`", s)))?;
+
+            return Ok(Self::Tuple(
+                split_top_level(inner)
+                    .into_iter()
+                    .map(Self::parse)
+                    .collect::<Result<_, _>>()?,
+            ));
+        }
+
+        match s.find('<') {
+            Some(idx) => {
+                let args = s[idx + 1..]
+                    .strip_suffix('>')
+                    .ok_or_else(|| DojoError::Bindgen(format!("Invalid generic type `{}`", s)))?;
+
+                Ok(Self::Path {
+                    path: s[..idx].trim_end_matches("::").to_string(),
+                    args: split_top_level(args)
+                        .into_iter()
+                        .map(Self::parse)
+                        .collect::<Result<_, _>>()?,
+                })
+            }
+            None => Ok(Self::Path {
+                path: s.to_string(),
+                args: vec![],
+            }),
+        }
+    }
+}
+
+/// Splits a list of types on the commas that are not nested in a type.
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (idx, c) in s.char_indices() {
+        match c {
+            '<' | '(' => depth += 1,
+            '>' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&s[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&s[start..]);
+    parts.into_iter().filter(|p| !p.trim().is_empty()).collect()
+}
+
+/// State of the bindings generation.
+struct Bindgen<'a> {
+    manifest: &'a DojoManifest,
+    /// Structs and enums declared in the ABIs, by Cairo path.
+    abi_types: HashMap<String, AbiType>,
+    /// Cairo paths of the structs and enums used by the models and systems.
+    used_types: BTreeSet<String>,
+}
+
+impl<'a> Bindgen<'a> {
+    fn new(manifest: &'a DojoManifest) -> Self {
+        let abis =
+            std::iter::once(&manifest.world.abi).chain(manifest.contracts.iter().map(|c| &c.abi));
+
+        Self {
+            manifest,
+            abi_types: abi_types(abis.flatten()),
+            used_types: BTreeSet::new(),
+        }
+    }
+
+    fn generate(mut self) -> Result<String, DojoError> {
+        // Models and systems are generated by namespace.
+        let mut namespaces: BTreeMap<&str, String> = BTreeMap::new();
+        let mut registrations = vec![];
+        let manifest = self.manifest;
+
+        for model in &manifest.models {
+            let (namespace, name) = split_tag(&model.tag)?;
+            let code = self.model(namespace, name, &model.members)?;
+            namespaces.entry(namespace).or_default().push_str(&code);
+            registrations.push(format!("{}::{}", rust_ident(namespace), name));
+        }
+
+        for contract in &manifest.contracts {
+            let (namespace, name) = split_tag(&contract.tag)?;
+            let code = self.contract(name, contract)?;
+            namespaces.entry(namespace).or_default().push_str(&code);
+        }
+
+        let types = self.types()?;
+
+        let mut out = String::new();
+        out.push_str("// Generated by dojo-bevy-bindgen from the Dojo manifest, do not edit.\n\n");
+        out.push_str("#[allow(unused_imports)]\n");
+        out.push_str("use ::dojo_bevy_plugin::starknet::core::types::{Felt, U256};\n");
+        out.push_str("#[allow(unused_imports)]\n");
+        out.push_str("use ::dojo_bevy_plugin::{DojoAppExt, DojoModel, DojoSystem, DojoType};\n");
+        out.push_str(&types);
+
+        for (namespace, code) in &namespaces {
+            let _ = write!(
+                out,
+                "\npub mod {} {{\n    #[allow(unused_imports)]\n    use super::*;\n{}}}\n",
+                rust_ident(namespace),
+                code
+            );
+        }
+
+        out.push_str("\n/// Registers all the models of the world.\n");
+        out.push_str("pub struct DojoBindingsPlugin;\n\n");
+        out.push_str("impl ::bevy::app::Plugin for DojoBindingsPlugin {\n");
+        out.push_str("    fn build(&self, app: &mut ::bevy::app::App) {\n");
+        for model in &registrations {
+            let _ = writeln!(out, "        app.register_dojo_model::<{}>();", model);
+        }
+        out.push_str("    }\n}\n");
+
+        Ok(out)
+    }
+
+    /// Generates the component of a model.
+    fn model(
+        &mut self,
+        namespace: &str,
+        name: &str,
+        members: &[ManifestMember],
+    ) -> Result<String, DojoError> {
+        let mut out = String::new();
+        let _ = writeln!(out, "\n    /// The `{}-{}` model.", namespace, name);
+        out.push_str(
+            "    #[derive(::bevy::prelude::Component, Debug, Clone, PartialEq, DojoModel)]\n",
+        );
+        let _ = writeln!(
+            out,
+            "    #[dojo(namespace = \"{}\", name = \"{}\")]",
+            namespace, name
+        );
+        let _ = writeln!(out, "    pub struct {} {{", name);
+
+        for member in members {
+            if member.key {
+                out.push_str("        #[dojo(key)]\n");
+            }
+            let ty = self.rust_type(&CairoType::parse(&member.ty)?)?;
+            let _ = writeln!(out, "        pub {}: {},", rust_ident(&member.name), ty);
+        }
+
+        out.push_str("    }\n");
+        Ok(out)
+    }
+
+    /// Generates the module of a contract, with a function building every system.
+    fn contract(&mut self, name: &str, contract: &ManifestContract) -> Result<String, DojoError> {
+        let mut out = String::new();
+        let _ = writeln!(out, "\n    /// The `{}` contract.", contract.tag);
+        let _ = writeln!(out, "    pub mod {} {{", rust_ident(name));
+        out.push_str("        #[allow(unused_imports)]\n        use super::*;\n\n");
+        let _ = writeln!(out, "        pub const TAG: &str = \"{}\";", contract.tag);
+
+        for system in contract.abi_systems() {
+            let mut params = vec![];
+            let mut args = vec![];
+            for input in &system.inputs {
+                let ident = rust_ident(&input.name);
+                let ty = self.rust_type(&CairoType::parse(&input.ty)?)?;
+                params.push(format!("{}: {}", ident, ty));
+                args.push(format!("DojoType::to_ty(&{})", ident));
+            }
+
+            let _ = writeln!(out, "\n        /// Builds the `{}` system.", system.name);
+            let _ = writeln!(
+                out,
+                "        pub fn {}({}) -> DojoSystem {{",
+                rust_ident(&system.name),
+                params.join(", ")
+            );
+            out.push_str("            DojoSystem {\n");
+            out.push_str("                contract_tag: TAG.to_string(),\n");
+            let _ = writeln!(
+                out,
+                "                name: \"{}\".to_string(),",
+                system.name
+            );
+            let _ = writeln!(out, "                args: vec![{}],", args.join(", "));
+            out.push_str("            }\n        }\n");
+        }
+
+        out.push_str("    }\n");
+        Ok(out)
+    }
+
+    /// Generates the structs and enums used by the models and the systems,
+    /// including the ones they use themselves.
+    fn types(&mut self) -> Result<String, DojoError> {
+        let mut out = String::new();
+        let mut generated = BTreeSet::new();
+        // The path of the type generated for every name.
+        let mut names: HashMap<String, String> = HashMap::new();
+
+        while let Some(path) = self
+            .used_types
+            .iter()
+            .find(|p| !generated.contains(*p))
+            .cloned()
+        {
+            generated.insert(path.clone());
+            let name = short_name(&path);
+
+            if let Some(other) = names.insert(name.to_string(), path.clone()) {
+                return Err(DojoError::Bindgen(format!(
+                    "Types `{}` and `{}` have the same name",
+                    other, path
+                )));
+            }
+
+            let fields = match &self.abi_types[&path] {
+                AbiType::Struct(fields) | AbiType::Enum(fields) => fields.clone(),
+            };
+            let is_struct = matches!(self.abi_types[&path], AbiType::Struct(_));
+
+            let _ = writeln!(out, "\n/// The `{}` type.", path);
+            out.push_str("#[derive(Debug, Clone, PartialEq, DojoType)]\n");
+
+            if is_struct {
+                let _ = writeln!(out, "pub struct {} {{", name);
+                for (field, ty) in fields {
+                    let ty = self.rust_type(&CairoType::parse(&ty)?)?;
+                    let _ = writeln!(out, "    pub {}: {},", rust_ident(&field), ty);
+                }
+            } else {
+                let _ = writeln!(out, "pub enum {} {{", name);
+                for (variant, ty) in fields {
+                    match CairoType::parse(&ty)? {
+                        CairoType::Tuple(items) if items.is_empty() => {
+                            let _ = writeln!(out, "    {},", variant);
+                        }
+                        ty => {
+                            let ty = self.rust_type(&ty)?;
+                            let _ = writeln!(out, "    {}({}),", variant, ty);
+                        }
+                    }
+                }
+            }
+
+            out.push_str("}\n");
+        }
+
+        Ok(out)
+    }
+
+    /// Returns the Rust type of a Cairo type, recording the structs
+    /// and enums to generate.
+    fn rust_type(&mut self, ty: &CairoType) -> Result<String, DojoError> {
+        let (path, args) = match ty {
+            CairoType::Tuple(items) => {
+                let items = items
+                    .iter()
+                    .map(|i| self.rust_type(i))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                return Ok(match items.len() {
+                    1 => format!("({},)", items[0]),
+                    _ => format!("({})", items.join(", ")),
+                });
+            }
+            CairoType::Path { path, args } => (path, args),
+        };
+
+        // Core types are matched on their name, since the manifest may not use the full path.
+        let name = short_name(path);
+
+        if maybe_core_type(path) {
+            match (name, args.as_slice()) {
+                (
+                    "u8" | "u16" | "u32" | "u64" | "u128" | "i8" | "i16" | "i32" | "i64" | "i128"
+                    | "bool",
+                    [],
+                ) => return Ok(name.to_string()),
+                ("felt252" | "ContractAddress" | "ClassHash" | "EthAddress", []) => {
+                    return Ok("Felt".to_string());
+                }
+                ("u256", []) => return Ok("U256".to_string()),
+                ("ByteArray", []) => return Ok("String".to_string()),
+                ("Array" | "Span", [item]) => return Ok(format!("Vec<{}>", self.rust_type(item)?)),
+                ("Option", [item]) => return Ok(format!("Option<{}>", self.rust_type(item)?)),
+                _ if path.starts_with("core::") => {
+                    return Err(DojoError::Bindgen(format!("Unsupported type `{}`", path)));
+                }
+                _ => {}
+            }
+        }
+
+        if !args.is_empty() {
+            return Err(DojoError::Bindgen(format!(
+                "Unsupported generic type `{}`",
+                path
+            )));
+        }
+
+        let path = resolve_type(&self.abi_types, path)?;
+        let name = short_name(&path).to_string();
+        self.used_types.insert(path);
+        Ok(name)
+    }
+}
+
+/// Returns the structs and enums declared in the ABI items, by Cairo path.
+pub(crate) fn abi_types<'a>(
+    items: impl IntoIterator<Item = &'a serde_json::Value>,
+) -> HashMap<String, AbiType> {
+    let mut abi_types = HashMap::new();
+
+    for item in items {
+        let (Some(kind), Some(name)) = (item["type"].as_str(), item["name"].as_str()) else {
+            continue;
+        };
+
+        match kind {
+            "struct" => {
+                abi_types.insert(
+                    name.to_string(),
+                    AbiType::Struct(abi_fields(&item["members"])),
+                );
+            }
+            "enum" => {
+                abi_types.insert(
+                    name.to_string(),
+                    AbiType::Enum(abi_fields(&item["variants"])),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    abi_types
+}
+
+/// Returns the Cairo path of a struct or an enum declared in the ABIs.
+///
+/// The manifest may only contain the name of the type, which is then
+/// resolved if a single type of the ABIs has this name.
+pub(crate) fn resolve_type(
+    abi_types: &HashMap<String, AbiType>,
+    path: &str,
+) -> Result<String, DojoError> {
+    if abi_types.contains_key(path) {
+        return Ok(path.to_string());
+    }
+
+    let mut candidates = abi_types
+        .keys()
+        .filter(|p| short_name(p) == short_name(path));
+
+    match (candidates.next(), candidates.next()) {
+        (Some(p), None) => Ok(p.clone()),
+        (Some(_), Some(_)) => Err(DojoError::Bindgen(format!("Ambiguous type `{}`", path))),
+        (None, _) => Err(DojoError::Bindgen(format!("Unsupported type `{}`", path))),
+    }
+}
+
+/// Returns true if the path may be a core type, which the manifest may name without its path.
+pub(crate) fn maybe_core_type(path: &str) -> bool {
+    path.starts_with("core::") || !path.contains("::")
+}
+
+/// Splits a tag into its namespace and name.
+fn split_tag(tag: &str) -> Result<(&str, &str), DojoError> {
+    tag.split_once('-')
+        .ok_or_else(|| DojoError::Bindgen(format!("Invalid tag `{}`", tag)))
+}
+
+/// Returns the last segment of a Cairo path.
+pub(crate) fn short_name(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+/// Escapes the Rust keywords.
+fn rust_ident(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern",
+        "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "static", "struct", "trait", "true", "type", "unsafe", "use",
+        "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override",
+        "priv", "try", "typeof", "unsized", "virtual", "yield",
+    ];
+
+    match name {
+        "self" | "Self" | "super" | "crate" => format!("{}_", name),
+        name if KEYWORDS.contains(&name) => format!("r#{}", name),
+        name => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::TEST_MANIFEST;
+
+    fn path(path: &str, args: Vec<CairoType>) -> CairoType {
+        CairoType::Path {
+            path: path.to_string(),
+            args,
+        }
+    }
+
+    #[test]
+    fn parse_cairo_type() {
+        assert_eq!(
+            CairoType::parse(" core::integer::u32 ").unwrap(),
+            path("core::integer::u32", vec![])
+        );
+        assert_eq!(
+            CairoType::parse("core::array::Span::<(core::integer::u8, core::felt252)>").unwrap(),
+            path(
+                "core::array::Span",
+                vec![CairoType::Tuple(vec![
+                    path("core::integer::u8", vec![]),
+                    path("core::felt252", vec![]),
+                ])]
+            )
+        );
+        assert_eq!(CairoType::parse("()").unwrap(), CairoType::Tuple(vec![]));
+        assert!(CairoType::parse("(u8, u16").is_err());
+        assert!(CairoType::parse("Array<u8").is_err());
+    }
+
+    #[test]
+    fn split_nested_types() {
+        assert_eq!(
+            split_top_level("u8, (u16, u32), Array<(u64, u8)>"),
+            ["u8", " (u16, u32)", " Array<(u64, u8)>"]
+        );
+        assert_eq!(split_top_level("u8,"), ["u8"]);
+        assert!(split_top_level("").is_empty());
+    }
+
+    #[test]
+    fn escape_rust_idents() {
+        assert_eq!(rust_ident("position"), "position");
+        assert_eq!(rust_ident("type"), "r#type");
+        assert_eq!(rust_ident("gen"), "r#gen");
+        assert_eq!(rust_ident("self"), "self_");
+    }
+
+    #[test]
+    fn generate_manifest_bindings() {
+        let manifest = DojoManifest::from_json(TEST_MANIFEST).unwrap();
+        let bindings = generate_bindings(&manifest).unwrap();
+
+        for expected in [
+            "pub enum Direction {\n    Left,\n    Jump(Vec2),\n}",
+            "pub struct Vec2 {\n    pub x: u32,\n    pub y: u32,\n}",
+            "#[dojo(namespace = \"di\", name = \"Position\")]",
+            "        #[dojo(key)]\n        pub player: Felt,",
+            "        pub r#type: Option<String>,",
+            "        pub balance: U256,",
+            "pub fn r#move(direction: Direction, path: Vec<(u8, Felt)>) -> DojoSystem {",
+            "app.register_dojo_model::<di::Position>();",
+        ] {
+            assert!(
+                bindings.contains(expected),
+                "`{}` not in:\n{}",
+                expected,
+                bindings
+            );
+        }
+
+        // View functions are not systems, and the core types are not generated.
+        assert!(!bindings.contains("fn get("));
+        assert!(!bindings.contains("pub struct u256"));
+    }
+
+    #[test]
+    fn duplicate_type_names() {
+        let mut manifest: serde_json::Value = serde_json::from_str(TEST_MANIFEST).unwrap();
+        let abi = manifest["contracts"][0]["abi"].as_array_mut().unwrap();
+        abi.push(serde_json::json!({
+            "type": "struct",
+            "name": "dojo_intro::types::Direction",
+            "members": [{ "name": "x", "type": "core::integer::u8" }]
+        }));
+        abi[0]["variants"][1]["type"] = "dojo_intro::types::Direction".into();
+        let manifest = DojoManifest::from_json(&manifest.to_string()).unwrap();
+
+        assert!(matches!(
+            generate_bindings(&manifest),
+            Err(DojoError::Bindgen(e))
+                if e.contains("dojo_intro::models::Direction")
+                    && e.contains("dojo_intro::types::Direction")
+        ));
+    }
+
+    #[test]
+    fn generic_user_type() {
+        let manifest = DojoManifest::from_json(&TEST_MANIFEST.replace(
+            r#""type": "Vec2""#,
+            r#""type": "dojo_intro::models::Vec2::<core::integer::u8>""#,
+        ))
+        .unwrap();
+
+        assert!(matches!(
+            generate_bindings(&manifest),
+            Err(DojoError::Bindgen(e)) if e.contains("generic type `dojo_intro::models::Vec2`")
+        ));
+    }
+
+    #[test]
+    fn unsupported_core_type() {
+        let manifest = DojoManifest::from_json(
+            &TEST_MANIFEST.replace("core::integer::u256", "core::integer::u512"),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            generate_bindings(&manifest),
+            Err(DojoError::Bindgen(e)) if e.contains("core::integer::u512")
+        ));
+    }
+}
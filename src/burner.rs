@@ -0,0 +1,616 @@
+//! Burner accounts for local development.
+//!
+//! The [`BurnerManager`] deploys new accounts funded by a master account (usually
+//! a predeployed account of Katana), and persists their keys to disk, in one file
+//! per world. Players can then pick one of the burners or create a new one at runtime.
+//!
+//! Burners are created in the background, the [`BurnerCreated`] and
+//! [`BurnerCreationFailed`] events being emitted once the deployment is done.
+//! A burner is persisted before being funded, so that its key is never lost:
+//! the burners not deployed yet are deployed again on startup, or with `resume`.
+//! The keys are stored in plain text: burners are only meant for development.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use starknet::accounts::{
+    Account, AccountFactory, ArgentAccountFactory, ConnectedAccount, OpenZeppelinAccountFactory,
+    SingleOwnerAccount,
+};
+use starknet::core::types::{BlockId, BlockTag, Call, Felt, FunctionCall};
+use starknet::core::utils::starknet_keccak;
+use starknet::providers::{AnyProvider, Provider};
+use starknet::signers::{LocalWallet, SigningKey};
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::error::DojoError;
+use crate::plugin::{
+    AccountSource, DojoResource, FrameBudget, PollBudget, TokioRuntime, connect_single_owner,
+    drain_finished, rpc_provider,
+};
+use crate::signer::DojoWallet;
+use crate::sync::DojoSet;
+use crate::tx::{DojoTxReceipt, TxConfig, wait_for_receipt};
+
+/// Address of the STRK token, the same on Katana and on the public networks.
+pub const STRK_ADDRESS: Felt =
+    Felt::from_hex_unchecked("0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d");
+
+/// Account contract of the burners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BurnerAccountKind {
+    OpenZeppelin,
+    Argent,
+}
+
+/// Configuration of the burner manager.
+#[derive(Clone)]
+pub struct BurnerConfig {
+    pub rpc_url: String,
+    /// The world the burners are created for, each world having its own burners.
+    pub world_address: Felt,
+    /// The funded account deploying the burners.
+    ///
+    /// The burners are funded with a nonce fetched from the node, so the master
+    /// account can't be the account connected by the `DojoResource`, whose nonce
+    /// is tracked locally: the deployment of the burners fails if it is.
+    pub master: AccountSource,
+    /// The class hash of the account contract, which must be declared.
+    pub class_hash: Felt,
+    pub kind: BurnerAccountKind,
+    /// Amount of fee token sent by the master account to every burner,
+    /// which pays for its own deployment. Nothing is sent if zero.
+    pub prefund: u128,
+    pub fee_token: Felt,
+    /// The directory where the burners are persisted.
+    pub dir: PathBuf,
+}
+
+impl BurnerConfig {
+    /// Creates the configuration of OpenZeppelin burners, prefunded with 1 STRK.
+    pub fn new(
+        rpc_url: String,
+        world_address: Felt,
+        master: AccountSource,
+        class_hash: Felt,
+    ) -> Self {
+        Self {
+            rpc_url,
+            world_address,
+            master,
+            class_hash,
+            kind: BurnerAccountKind::OpenZeppelin,
+            prefund: 1_000_000_000_000_000_000,
+            fee_token: STRK_ADDRESS,
+            dir: PathBuf::from("burners"),
+        }
+    }
+
+    /// Returns the file where the burners of the world are persisted.
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(format!("{:#x}.json", self.world_address))
+    }
+}
+
+/// A burner account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Burner {
+    pub address: Felt,
+    pub private_key: Felt,
+    pub class_hash: Felt,
+    pub kind: BurnerAccountKind,
+    /// False until the burner is deployed, a burner being persisted before it's funded.
+    pub deployed: bool,
+}
+
+impl Burner {
+    /// Returns the source to connect to the burner with.
+    pub fn account_source(&self) -> AccountSource {
+        AccountSource::PrivateKey {
+            address: self.address,
+            private_key: self.private_key,
+        }
+    }
+}
+
+/// Content of the file of the burners of a world.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BurnerStore {
+    burners: Vec<Burner>,
+    active: Option<Felt>,
+}
+
+/// This event is emitted when a burner has been deployed and persisted.
+#[derive(Event, Debug)]
+pub struct BurnerCreated {
+    pub burner: Burner,
+}
+
+/// This event is emitted when a burner could not be created.
+///
+/// If the burner was persisted already, its deployment can be retried with `resume`.
+#[derive(Event, Debug)]
+pub struct BurnerCreationFailed {
+    pub error: DojoError,
+}
+
+/// Manager of the burner accounts of a world.
+#[derive(Resource)]
+pub struct BurnerManager {
+    config: BurnerConfig,
+    store: BurnerStore,
+    pending: VecDeque<JoinHandle<Result<BurnerTask, DojoError>>>,
+    /// The addresses of the burners being deployed.
+    deploying: HashSet<Felt>,
+    // The burners are funded one at a time, to keep the nonce of the master account in order.
+    master_lock: Arc<Mutex<()>>,
+}
+
+/// Outcome of a burner task.
+enum BurnerTask {
+    /// A burner was generated, to be persisted before being funded and deployed.
+    Generated(Burner),
+    /// The deployment of the burner at the address is done.
+    Deployed(Felt, Result<(), DojoError>),
+}
+
+impl BurnerManager {
+    /// Loads the burners of the world, if any.
+    pub fn load(config: BurnerConfig) -> Result<Self, DojoError> {
+        let path = config.path();
+
+        let store = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            BurnerStore::default()
+        };
+
+        Ok(Self::with_store(config, store))
+    }
+
+    /// Creates the manager of the given burners.
+    fn with_store(config: BurnerConfig, store: BurnerStore) -> Self {
+        Self {
+            config,
+            store,
+            pending: VecDeque::new(),
+            deploying: HashSet::new(),
+            master_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Returns the burners of the world, including the ones not deployed yet.
+    pub fn burners(&self) -> &[Burner] {
+        &self.store.burners
+    }
+
+    /// Returns the burner selected last, if any.
+    pub fn active(&self) -> Option<&Burner> {
+        let address = self.store.active?;
+        self.store.burners.iter().find(|b| b.address == address)
+    }
+
+    /// Returns the number of burners being created or deployed.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Selects the burner, which must be deployed, and connects the Dojo resource to it.
+    ///
+    /// The selection is persisted, to be restored with `connect_active`.
+    pub fn select(
+        &mut self,
+        tokio: &TokioRuntime,
+        dojo: &mut DojoResource,
+        address: Felt,
+    ) -> Result<(), DojoError> {
+        let burner = self
+            .store
+            .burners
+            .iter()
+            .find(|b| b.address == address && b.deployed)
+            .ok_or(DojoError::BurnerNotFound(address))?;
+
+        dojo.connect_account(
+            tokio,
+            self.config.rpc_url.clone(),
+            burner.address,
+            burner.private_key,
+        );
+
+        self.store.active = Some(address);
+        self.save()
+    }
+
+    /// Connects the Dojo resource to the burner selected last.
+    ///
+    /// Returns false if no burner was selected.
+    pub fn connect_active(&self, tokio: &TokioRuntime, dojo: &mut DojoResource) -> bool {
+        let Some(burner) = self.active() else {
+            return false;
+        };
+
+        dojo.connect_account(
+            tokio,
+            self.config.rpc_url.clone(),
+            burner.address,
+            burner.private_key,
+        );
+        true
+    }
+
+    /// Queues the creation of a new burner.
+    ///
+    /// The burner is persisted, funded by the master account and deployed
+    /// before the `BurnerCreated` event is emitted.
+    pub fn create(&mut self, tokio: &TokioRuntime) {
+        info!("Creating a burner account.");
+        let config = self.config.clone();
+
+        let task = tokio
+            .runtime
+            .spawn(async move { generate_burner(config).await.map(BurnerTask::Generated) });
+        self.pending.push_back(task);
+    }
+
+    /// Queues the deployment of the burners not deployed yet, after a failure.
+    ///
+    /// A burner already funded is not funded again.
+    pub fn resume(&mut self, tokio: &TokioRuntime, dojo: &DojoResource) {
+        let burners: Vec<Burner> = self
+            .store
+            .burners
+            .iter()
+            .filter(|b| !b.deployed && !self.deploying.contains(&b.address))
+            .cloned()
+            .collect();
+
+        for burner in burners {
+            self.deploy(tokio, dojo, burner);
+        }
+    }
+
+    /// Queues the funding and the deployment of a persisted burner.
+    fn deploy(&mut self, tokio: &TokioRuntime, dojo: &DojoResource, burner: Burner) {
+        info!("Deploying the burner {:#x}.", burner.address);
+        let config = self.config.clone();
+        let master_lock = self.master_lock.clone();
+        let game_account = dojo.sn.account.as_ref().map(|a| a.sender().address());
+        self.deploying.insert(burner.address);
+
+        let task = tokio.runtime.spawn(async move {
+            let result = deploy_burner(config, &burner, master_lock, game_account).await;
+            Ok(BurnerTask::Deployed(burner.address, result))
+        });
+        self.pending.push_back(task);
+    }
+
+    /// Writes the burners to the file of the world.
+    fn save(&self) -> Result<(), DojoError> {
+        std::fs::create_dir_all(&self.config.dir)?;
+        std::fs::write(
+            self.config.path(),
+            serde_json::to_string_pretty(&self.store)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// Plugin managing the burners, with the `BurnerManager` resource.
+///
+/// The `DojoPlugin` must be added before this plugin.
+pub struct BurnerPlugin {
+    pub config: BurnerConfig,
+}
+
+impl Plugin for BurnerPlugin {
+    fn build(&self, app: &mut App) {
+        let manager = BurnerManager::load(self.config.clone()).unwrap_or_else(|e| {
+            error!("Failed to load the burners: {}", e);
+            BurnerManager::with_store(self.config.clone(), BurnerStore::default())
+        });
+
+        app.insert_resource(manager);
+        app.add_event::<BurnerCreated>();
+        app.add_event::<BurnerCreationFailed>();
+        app.add_systems(Startup, resume_burners);
+        app.add_systems(Update, check_burner_tasks.in_set(DojoSet::Poll));
+    }
+}
+
+/// Resumes the deployment of the burners persisted before being deployed.
+fn resume_burners(
+    tokio: Res<TokioRuntime>,
+    dojo: Res<DojoResource>,
+    mut manager: ResMut<BurnerManager>,
+) {
+    manager.resume(&tokio, &dojo);
+}
+
+/// Persists the generated burners before deploying them, and emits the burners events.
+fn check_burner_tasks(
+    tokio: Res<TokioRuntime>,
+    dojo: Res<DojoResource>,
+    mut manager: ResMut<BurnerManager>,
+    mut ev_created: EventWriter<BurnerCreated>,
+    mut ev_failed: EventWriter<BurnerCreationFailed>,
+) {
+    let mut budget = FrameBudget::new(PollBudget::default());
+
+    for result in drain_finished(&tokio, &mut manager.pending, &mut budget) {
+        match result.map_err(DojoError::from).and_then(|r| r) {
+            Ok(BurnerTask::Generated(burner)) => {
+                manager.store.burners.push(burner.clone());
+
+                // The burner is only funded once its key is persisted.
+                match manager.save() {
+                    Ok(()) => manager.deploy(&tokio, &dojo, burner),
+                    Err(error) => {
+                        error!("Failed to save the burners: {}", error);
+                        manager.store.burners.pop();
+                        ev_failed.write(BurnerCreationFailed { error });
+                    }
+                }
+            }
+            Ok(BurnerTask::Deployed(address, Ok(()))) => {
+                manager.deploying.remove(&address);
+                info!("Burner created: {:#x}", address);
+
+                let burners = &mut manager.store.burners;
+                let Some(burner) = burners.iter_mut().find(|b| b.address == address) else {
+                    continue;
+                };
+                burner.deployed = true;
+                let burner = burner.clone();
+
+                if let Err(e) = manager.save() {
+                    error!("Failed to save the burners: {}", e);
+                }
+
+                ev_created.write(BurnerCreated { burner });
+            }
+            Ok(BurnerTask::Deployed(address, Err(error))) => {
+                manager.deploying.remove(&address);
+                error!("Failed to deploy the burner {:#x}: {}", address, error);
+                ev_failed.write(BurnerCreationFailed { error });
+            }
+            Err(error) => {
+                error!("Failed to create a burner: {}", error);
+                ev_failed.write(BurnerCreationFailed { error });
+            }
+        }
+    }
+}
+
+/// Generates the key of a new burner, and computes its address.
+async fn generate_burner(config: BurnerConfig) -> Result<Burner, DojoError> {
+    let key = SigningKey::from_random();
+    let private_key = key.secret_scalar();
+    let provider = rpc_provider(&config.rpc_url)?;
+    let chain_id = provider.chain_id().await?;
+
+    let address = match config.kind {
+        BurnerAccountKind::OpenZeppelin => {
+            let factory = OpenZeppelinAccountFactory::new(
+                config.class_hash,
+                chain_id,
+                LocalWallet::from(key),
+                provider,
+            )
+            .await
+            .map_err(|e| DojoError::Burner(e.to_string()))?;
+            factory.deploy_v3(burner_salt(private_key)).address()
+        }
+        BurnerAccountKind::Argent => {
+            let factory = ArgentAccountFactory::new(
+                config.class_hash,
+                chain_id,
+                None,
+                LocalWallet::from(key),
+                provider,
+            )
+            .await
+            .map_err(|e| DojoError::Burner(e.to_string()))?;
+            factory.deploy_v3(burner_salt(private_key)).address()
+        }
+    };
+
+    Ok(Burner {
+        address,
+        private_key,
+        class_hash: config.class_hash,
+        kind: config.kind,
+        deployed: false,
+    })
+}
+
+/// Funds and deploys a persisted burner from the master account.
+///
+/// Fails if the master account is the account of the game, sending the transactions
+/// of the `DojoResource`.
+async fn deploy_burner(
+    config: BurnerConfig,
+    burner: &Burner,
+    master_lock: Arc<Mutex<()>>,
+    game_account: Option<Felt>,
+) -> Result<(), DojoError> {
+    let master = connect_single_owner(config.rpc_url.clone(), config.master.clone(), None).await?;
+
+    if config.prefund > 0 && game_account == Some(master.address()) {
+        return Err(DojoError::Burner(format!(
+            "The master account {:#x} is the account of the game",
+            master.address()
+        )));
+    }
+
+    let signer = LocalWallet::from(SigningKey::from_secret_scalar(burner.private_key));
+    let salt = burner_salt(burner.private_key);
+    let provider = rpc_provider(&config.rpc_url)?;
+    let chain_id = master.chain_id();
+
+    match burner.kind {
+        BurnerAccountKind::OpenZeppelin => {
+            let factory =
+                OpenZeppelinAccountFactory::new(burner.class_hash, chain_id, signer, provider)
+                    .await
+                    .map_err(|e| DojoError::Burner(e.to_string()))?;
+            deploy_account(&factory, &master, &master_lock, &config, salt).await
+        }
+        BurnerAccountKind::Argent => {
+            let factory =
+                ArgentAccountFactory::new(burner.class_hash, chain_id, None, signer, provider)
+                    .await
+                    .map_err(|e| DojoError::Burner(e.to_string()))?;
+            deploy_account(&factory, &master, &master_lock, &config, salt).await
+        }
+    }
+}
+
+/// Returns the salt of the deployment of a burner, its public key.
+fn burner_salt(private_key: Felt) -> Felt {
+    SigningKey::from_secret_scalar(private_key)
+        .verifying_key()
+        .scalar()
+}
+
+/// Funds the address of the account with the master account, then deploys it.
+///
+/// An attempt may have failed after the funding or the deployment: the account
+/// is not funded again if its balance covers the prefund, nor deployed again.
+async fn deploy_account<F>(
+    factory: &F,
+    master: &SingleOwnerAccount<AnyProvider, DojoWallet>,
+    master_lock: &Mutex<()>,
+    config: &BurnerConfig,
+    salt: Felt,
+) -> Result<(), DojoError>
+where
+    F: AccountFactory + Sync,
+{
+    let deployment = factory.deploy_v3(salt);
+    let address = deployment.address();
+    let provider = master.provider();
+
+    if provider
+        .get_class_hash_at(BlockId::Tag(BlockTag::Pending), address)
+        .await
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    if config.prefund > 0 && !is_funded(provider, config, address).await? {
+        let _guard = master_lock.lock().await;
+
+        let transfer = Call {
+            to: config.fee_token,
+            selector: starknet_keccak(b"transfer"),
+            calldata: vec![address, config.prefund.into(), Felt::ZERO],
+        };
+
+        let result = master
+            .execute_v3(vec![transfer])
+            .send()
+            .await
+            .map_err(|e| DojoError::Burner(format!("Failed to fund the burner: {}", e)))?;
+
+        wait_for_success(master.provider(), result.transaction_hash).await?;
+    }
+
+    let result = deployment
+        .send()
+        .await
+        .map_err(|e| DojoError::Burner(format!("Failed to deploy the burner: {}", e)))?;
+
+    wait_for_success(master.provider(), result.transaction_hash).await?;
+
+    Ok(())
+}
+
+/// Returns true if the fee token balance of the address covers the prefund.
+async fn is_funded(
+    provider: &AnyProvider,
+    config: &BurnerConfig,
+    address: Felt,
+) -> Result<bool, DojoError> {
+    let call = FunctionCall {
+        contract_address: config.fee_token,
+        entry_point_selector: starknet_keccak(b"balance_of"),
+        calldata: vec![address],
+    };
+
+    let balance = provider.call(call, BlockId::Tag(BlockTag::Pending)).await?;
+
+    // The balance is a u256, as its low and high parts.
+    Ok(match balance.as_slice() {
+        [low, high] => *high > Felt::ZERO || *low >= Felt::from(config.prefund),
+        _ => false,
+    })
+}
+
+/// Waits for the receipt of the transaction, failing if it reverted.
+async fn wait_for_success(provider: &AnyProvider, transaction_hash: Felt) -> Result<(), DojoError> {
+    let receipt = wait_for_receipt(provider, transaction_hash, TxConfig::default())
+        .await
+        .map_err(DojoError::Burner)?;
+
+    match DojoTxReceipt::from(receipt).revert_reason {
+        Some(reason) => Err(DojoError::Burner(format!(
+            "Transaction {:#x} reverted: {}",
+            transaction_hash, reason
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_deployment_state() {
+        let store: BurnerStore = serde_json::from_value(serde_json::json!({
+            "burners": [
+                {
+                    "address": "0x1",
+                    "private_key": "0x2",
+                    "class_hash": "0x3",
+                    "kind": "OpenZeppelin",
+                    "deployed": true
+                },
+                {
+                    "address": "0x4",
+                    "private_key": "0x5",
+                    "class_hash": "0x3",
+                    "kind": "Argent",
+                    "deployed": false
+                }
+            ],
+            "active": "0x1"
+        }))
+        .unwrap();
+
+        assert!(store.burners[0].deployed);
+        assert!(!store.burners[1].deployed);
+        assert_eq!(store.active, Some(Felt::ONE));
+    }
+
+    #[test]
+    fn store_deployment_state_required() {
+        let store = serde_json::from_value::<BurnerStore>(serde_json::json!({
+            "burners": [
+                {
+                    "address": "0x1",
+                    "private_key": "0x2",
+                    "class_hash": "0x3",
+                    "kind": "OpenZeppelin"
+                }
+            ]
+        }));
+
+        assert!(store.is_err());
+    }
+}
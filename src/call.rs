@@ -0,0 +1,48 @@
+//! Read-only calls.
+//!
+//! View functions are executed with `starknet_call` on the tokio runtime,
+//! without sending any transaction. The result of every call is emitted
+//! as a Bevy event by the Dojo plugin.
+
+use bevy::prelude::*;
+use starknet::core::types::{BlockId, Call, Felt, FunctionCall};
+use starknet::providers::Provider;
+
+/// Identifier of a call queued with `DojoResource::queue_call`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DojoCallId(pub u64);
+
+/// This event is emitted when a call returned.
+#[derive(Event, Debug)]
+pub struct DojoCallResult {
+    pub id: DojoCallId,
+    pub result: Vec<Felt>,
+}
+
+/// This event is emitted when a call could not be executed.
+#[derive(Event, Debug)]
+pub struct DojoCallFailed {
+    pub id: DojoCallId,
+    pub error: String,
+}
+
+/// Executes the call against the given block.
+pub(crate) async fn execute_call<P>(
+    provider: &P,
+    call: Call,
+    block_id: BlockId,
+) -> Result<Vec<Felt>, String>
+where
+    P: Provider + Sync,
+{
+    let request = FunctionCall {
+        contract_address: call.to,
+        entry_point_selector: call.selector,
+        calldata: call.calldata,
+    };
+
+    provider
+        .call(request, block_id)
+        .await
+        .map_err(|e| e.to_string())
+}
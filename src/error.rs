@@ -0,0 +1,117 @@
+//! Errors reported by the Dojo plugin.
+
+use dojo_types::schema::Ty;
+use starknet::core::types::Felt;
+use starknet::providers::ProviderError;
+use tokio::task::JoinError;
+
+/// Errors that can occur while connecting to Starknet or Torii, or loading the Dojo data.
+#[derive(Debug, thiserror::Error)]
+pub enum DojoError {
+    #[error("Invalid URL `{url}`: {source}")]
+    InvalidUrl {
+        url: String,
+        #[source]
+        source: url::ParseError,
+    },
+    #[error("Starknet RPC unreachable: {0}")]
+    RpcUnreachable(#[from] ProviderError),
+    #[error("Failed to fetch predeployed accounts: {0}")]
+    PredeployedAccounts(#[from] reqwest::Error),
+    #[error("Invalid predeployed accounts response: {0}")]
+    InvalidResponse(String),
+    #[error("Chain id mismatch: expected {expected:#x}, got {actual:#x}")]
+    ChainIdMismatch { expected: Felt, actual: Felt },
+    #[error("No predeployed account at index {0}")]
+    AccountIndexMissing(usize),
+    #[error("Predeployed account at index {0} is hidden")]
+    HiddenAccount(usize),
+    #[error("Torii error: {0}")]
+    Torii(#[from] torii_grpc_client::Error),
+    #[error("Torii connection lost")]
+    ToriiConnectionLost,
+    #[error("Failed to read file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Runtime error: {0}")]
+    Runtime(#[from] JoinError),
+    #[error("Failed to generate bindings: {0}")]
+    Bindgen(String),
+    #[error("No manifest loaded")]
+    ManifestMissing,
+    #[error("Contract `{0}` not found in the manifest")]
+    ContractNotFound(String),
+    #[error("System `{system}` not found in `{contract}`")]
+    SystemNotFound { contract: String, system: String },
+    #[error("System `{system}` expects {expected} arguments, got {actual}")]
+    SystemArguments {
+        system: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("Argument `{argument}` of system `{system}` expects `{expected}`, got `{found}`")]
+    SystemArgumentType {
+        system: String,
+        argument: String,
+        expected: String,
+        found: String,
+    },
+    #[error(transparent)]
+    Model(#[from] DojoModelError),
+    #[error("Session expired at {0}")]
+    SessionExpired(u64),
+    #[error("Call to {selector:#x} on {contract_address:#x} not allowed by the session")]
+    SessionPolicy {
+        contract_address: Felt,
+        selector: Felt,
+    },
+    #[error("The sender of a session can't be a session")]
+    SessionSender,
+    #[error("Failed to sign: {0}")]
+    Signing(String),
+    #[error(transparent)]
+    Signer(#[from] DojoSignerError),
+    #[error("Burner error: {0}")]
+    Burner(String),
+    #[error("No burner with address {0:#x}")]
+    BurnerNotFound(Felt),
+}
+
+/// Errors reported by the signers of the accounts.
+#[derive(Debug, thiserror::Error)]
+pub enum DojoSignerError {
+    #[error("Remote signer error: {0}")]
+    Remote(#[from] reqwest::Error),
+    #[error("Failed to sign: {0}")]
+    Signing(String),
+    #[error("Failed to load keystore: {0}")]
+    Keystore(String),
+}
+
+/// Errors that can occur while converting Dojo models from and to Rust types.
+#[derive(Debug, thiserror::Error)]
+pub enum DojoModelError {
+    #[error("Expected model `{expected}`, found `{found}`")]
+    UnexpectedModel { expected: String, found: String },
+    #[error("Member `{member}` missing in `{ty}`")]
+    MissingMember { ty: String, member: String },
+    #[error("Expected type `{expected}`, found `{found}`")]
+    TypeMismatch { expected: String, found: String },
+    #[error("No variant selected for enum `{ty}`")]
+    MissingVariant { ty: String },
+    #[error("Unknown variant `{variant}` for enum `{ty}`")]
+    UnknownVariant { ty: String, variant: String },
+    #[error("Failed to serialize: {0}")]
+    Serialization(String),
+}
+
+impl DojoModelError {
+    /// Builds a type mismatch error from the expected type name and the found type.
+    pub fn type_mismatch(expected: &str, found: &Ty) -> Self {
+        Self::TypeMismatch {
+            expected: expected.to_string(),
+            found: found.name(),
+        }
+    }
+}
@@ -1,3 +1,39 @@
+mod account;
+mod bindgen;
+mod burner;
+mod call;
+mod error;
+mod manifest;
+mod model;
 mod plugin;
+mod reconnect;
+mod signer;
+mod state;
+mod sync;
+mod system;
+mod tx;
 
+pub use account::*;
+pub use bindgen::*;
+pub use burner::*;
+pub use call::*;
+pub use error::*;
+pub use manifest::*;
+pub use model::*;
 pub use plugin::*;
+pub use reconnect::*;
+pub use signer::*;
+pub use state::*;
+pub use sync::*;
+pub use system::*;
+pub use tx::*;
+
+pub use dojo_bevy_macros::{DojoModel, DojoType};
+
+// Lets the derive macros be used by the tests of the crate.
+#[cfg(test)]
+extern crate self as dojo_bevy_plugin;
+
+// Re-exported for the derive macros and bindings.
+pub use dojo_types;
+pub use starknet;
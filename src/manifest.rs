@@ -0,0 +1,394 @@
+//! Dojo manifest.
+//!
+//! The manifest is generated by `sozo migrate` (`manifest_<profile>.json`),
+//! and contains the addresses of the world and the contracts, along with
+//! the models and systems registered in the world.
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
+use starknet::core::types::Felt;
+use std::path::Path;
+
+use crate::error::DojoError;
+
+/// Dojo manifest, as generated by `sozo migrate`.
+#[derive(Resource, Debug, Clone, Deserialize)]
+pub struct DojoManifest {
+    pub world: ManifestWorld,
+    #[serde(default)]
+    pub contracts: Vec<ManifestContract>,
+    #[serde(default)]
+    pub models: Vec<ManifestModel>,
+    #[serde(default)]
+    pub events: Vec<ManifestEvent>,
+}
+
+/// The world contract.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestWorld {
+    pub address: Felt,
+    pub class_hash: Felt,
+    pub seed: String,
+    pub name: String,
+    #[serde(default)]
+    pub abi: Vec<serde_json::Value>,
+}
+
+/// A contract registered in the world.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestContract {
+    /// The tag of the contract, `<namespace>-<name>`.
+    pub tag: String,
+    pub address: Felt,
+    pub class_hash: Felt,
+    pub selector: Felt,
+    /// The names of the systems (external functions) of the contract.
+    #[serde(default)]
+    pub systems: Vec<String>,
+    #[serde(default)]
+    pub abi: Vec<serde_json::Value>,
+}
+
+/// A model registered in the world.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestModel {
+    /// The tag of the model, `<namespace>-<name>`.
+    pub tag: String,
+    pub class_hash: Felt,
+    pub selector: Felt,
+    #[serde(default)]
+    pub members: Vec<ManifestMember>,
+}
+
+/// An event registered in the world.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestEvent {
+    /// The tag of the event, `<namespace>-<name>`.
+    pub tag: String,
+    pub class_hash: Felt,
+    pub selector: Felt,
+    #[serde(default)]
+    pub members: Vec<ManifestMember>,
+}
+
+/// A system of a contract, as declared in its ABI.
+#[derive(Debug, Clone)]
+pub struct ManifestSystem {
+    pub name: String,
+    pub inputs: Vec<ManifestInput>,
+}
+
+/// An input of a system.
+#[derive(Debug, Clone)]
+pub struct ManifestInput {
+    pub name: String,
+    /// The Cairo type of the input.
+    pub ty: String,
+}
+
+/// A member of a model or an event.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestMember {
+    pub name: String,
+    /// The Cairo type of the member.
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub key: bool,
+}
+
+impl DojoManifest {
+    /// Parses a manifest from its JSON content.
+    pub fn from_json(json: &str) -> Result<Self, DojoError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Loads the manifest from the given file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, DojoError> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json)
+    }
+
+    /// Loads the `manifest_<profile>.json` file from the given directory,
+    /// usually the root of the Dojo project.
+    pub fn from_profile(dir: impl AsRef<Path>, profile: &str) -> Result<Self, DojoError> {
+        Self::from_path(dir.as_ref().join(format!("manifest_{}.json", profile)))
+    }
+
+    /// Returns the address of the world.
+    pub fn world_address(&self) -> Felt {
+        self.world.address
+    }
+
+    /// Returns the contract with the given tag (e.g. `di-actions`).
+    pub fn contract(&self, tag: &str) -> Option<&ManifestContract> {
+        self.contracts.iter().find(|c| c.tag == tag)
+    }
+
+    /// Returns the model with the given tag (e.g. `di-Position`).
+    pub fn model(&self, tag: &str) -> Option<&ManifestModel> {
+        self.models.iter().find(|m| m.tag == tag)
+    }
+
+    /// Returns the tags of all the models registered in the world.
+    pub fn model_tags(&self) -> impl Iterator<Item = &str> {
+        self.models.iter().map(|m| m.tag.as_str())
+    }
+}
+
+impl ManifestContract {
+    /// Returns true if the contract exposes the given system.
+    pub fn has_system(&self, name: &str) -> bool {
+        self.systems.iter().any(|s| s == name)
+    }
+
+    /// Returns the systems declared in the ABI of the contract.
+    ///
+    /// Only the external functions are kept, and only the ones listed
+    /// in `systems`, if any.
+    pub fn abi_systems(&self) -> Vec<ManifestSystem> {
+        let functions = self
+            .abi
+            .iter()
+            .flat_map(|item| match item["type"].as_str() {
+                Some("interface") => item["items"].as_array().cloned().unwrap_or_default(),
+                Some("function") => vec![item.clone()],
+                _ => vec![],
+            });
+
+        functions
+            .filter(|f| f["type"] == "function" && f["state_mutability"] == "external")
+            .filter_map(|f| {
+                let name = f["name"].as_str()?.to_string();
+                let inputs = abi_fields(&f["inputs"])
+                    .into_iter()
+                    .map(|(name, ty)| ManifestInput { name, ty })
+                    .collect();
+
+                (self.systems.is_empty() || self.has_system(&name))
+                    .then_some(ManifestSystem { name, inputs })
+            })
+            .collect()
+    }
+
+    /// Returns the system with the given name, as declared in the ABI.
+    pub fn abi_system(&self, name: &str) -> Option<ManifestSystem> {
+        self.abi_systems().into_iter().find(|s| s.name == name)
+    }
+}
+
+/// Returns the name and type of the members of a struct, or the variants of an enum.
+pub(crate) fn abi_fields(value: &Value) -> Vec<(String, String)> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| {
+            Some((
+                m["name"].as_str()?.to_string(),
+                m["type"].as_str()?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// The manifest used by the tests of the systems and of the bindings.
+#[cfg(test)]
+pub(crate) const TEST_MANIFEST: &str = r#"{
+    "world": { "address": "0x1", "class_hash": "0x2", "seed": "di", "name": "intro" },
+    "contracts": [
+        {
+            "tag": "di-actions",
+            "address": "0x3",
+            "class_hash": "0x4",
+            "selector": "0x5",
+            "abi": [
+                {
+                    "type": "enum",
+                    "name": "dojo_intro::models::Direction",
+                    "variants": [
+                        { "name": "Left", "type": "()" },
+                        { "name": "Jump", "type": "dojo_intro::models::Vec2" }
+                    ]
+                },
+                {
+                    "type": "struct",
+                    "name": "dojo_intro::models::Vec2",
+                    "members": [
+                        { "name": "x", "type": "core::integer::u32" },
+                        { "name": "y", "type": "core::integer::u32" }
+                    ]
+                },
+                {
+                    "type": "struct",
+                    "name": "core::integer::u256",
+                    "members": [
+                        { "name": "low", "type": "core::integer::u128" },
+                        { "name": "high", "type": "core::integer::u128" }
+                    ]
+                },
+                {
+                    "type": "interface",
+                    "name": "dojo_intro::systems::actions::IActions",
+                    "items": [
+                        {
+                            "type": "function",
+                            "name": "move",
+                            "inputs": [
+                                { "name": "direction", "type": "dojo_intro::models::Direction" },
+                                { "name": "path", "type": "core::array::Span::<(core::integer::u8, core::felt252)>" }
+                            ],
+                            "outputs": [],
+                            "state_mutability": "external"
+                        },
+                        {
+                            "type": "function",
+                            "name": "get",
+                            "inputs": [],
+                            "outputs": [],
+                            "state_mutability": "view"
+                        }
+                    ]
+                }
+            ]
+        }
+    ],
+    "models": [
+        {
+            "tag": "di-Position",
+            "class_hash": "0x6",
+            "selector": "0x7",
+            "members": [
+                { "name": "player", "type": "ContractAddress", "key": true },
+                { "name": "vec", "type": "Vec2", "key": false },
+                { "name": "type", "type": "core::option::Option::<core::byte_array::ByteArray>", "key": false },
+                { "name": "balance", "type": "core::integer::u256", "key": false }
+            ]
+        }
+    ]
+}"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+        "world": {
+            "address": "0x1234",
+            "class_hash": "0x1",
+            "seed": "di",
+            "name": "intro"
+        },
+        "contracts": [
+            {
+                "tag": "di-actions",
+                "address": "0xabc",
+                "class_hash": "0x2",
+                "selector": "0x3",
+                "systems": ["spawn", "move"]
+            }
+        ],
+        "models": [
+            {
+                "tag": "di-Position",
+                "class_hash": "0x4",
+                "selector": "0x5",
+                "members": [
+                    { "name": "player", "type": "ContractAddress", "key": true },
+                    { "name": "x", "type": "u32", "key": false }
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn from_json() {
+        let manifest = DojoManifest::from_json(MANIFEST).unwrap();
+
+        assert_eq!(manifest.world_address(), Felt::from(0x1234_u64));
+        assert!(manifest.world.abi.is_empty());
+        assert!(manifest.events.is_empty());
+
+        let actions = manifest.contract("di-actions").unwrap();
+        assert_eq!(actions.address, Felt::from(0xabc_u64));
+        assert!(actions.has_system("move"));
+        assert!(!actions.has_system("upgrade"));
+        assert!(manifest.contract("di-other").is_none());
+
+        let position = manifest.model("di-Position").unwrap();
+        assert_eq!(position.members.len(), 2);
+        assert!(position.members[0].key);
+        assert_eq!(position.members[1].ty, "u32");
+        assert_eq!(manifest.model_tags().collect::<Vec<_>>(), ["di-Position"]);
+    }
+
+    #[test]
+    fn from_json_invalid() {
+        assert!(matches!(
+            DojoManifest::from_json(r#"{ "contracts": [] }"#),
+            Err(DojoError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn abi_systems() {
+        let mut contract = DojoManifest::from_json(MANIFEST)
+            .unwrap()
+            .contract("di-actions")
+            .cloned()
+            .unwrap();
+
+        contract.abi = serde_json::from_str(
+            r#"[
+                {
+                    "type": "interface",
+                    "name": "dojo_intro::systems::actions::IActions",
+                    "items": [
+                        {
+                            "type": "function",
+                            "name": "move",
+                            "inputs": [{ "name": "direction", "type": "dojo_intro::models::Direction" }],
+                            "outputs": [],
+                            "state_mutability": "external"
+                        },
+                        {
+                            "type": "function",
+                            "name": "get",
+                            "inputs": [],
+                            "outputs": [],
+                            "state_mutability": "view"
+                        }
+                    ]
+                },
+                {
+                    "type": "function",
+                    "name": "spawn",
+                    "inputs": [],
+                    "outputs": [],
+                    "state_mutability": "external"
+                },
+                {
+                    "type": "function",
+                    "name": "upgrade",
+                    "inputs": [{ "name": "class_hash", "type": "core::starknet::class_hash::ClassHash" }],
+                    "outputs": [],
+                    "state_mutability": "external"
+                }
+            ]"#,
+        )
+        .unwrap();
+
+        // View functions and functions missing from `systems` are not systems.
+        let systems: Vec<_> = contract.abi_systems().into_iter().map(|s| s.name).collect();
+        assert_eq!(systems, ["move", "spawn"]);
+
+        let system = contract.abi_system("move").unwrap();
+        assert_eq!(system.inputs.len(), 1);
+        assert_eq!(system.inputs[0].name, "direction");
+        assert_eq!(system.inputs[0].ty, "dojo_intro::models::Direction");
+        assert!(contract.abi_system("get").is_none());
+
+        contract.systems.clear();
+        assert!(contract.abi_system("upgrade").is_some());
+    }
+}
@@ -0,0 +1,436 @@
+//! Mapping between Rust types and Dojo models.
+//!
+//! Torii sends the models as `dojo_types::schema::Struct`, which are dynamically typed.
+//! The [`DojoType`] trait converts a Rust type from and to a `Ty`, and the [`DojoModel`]
+//! trait maps a Rust struct to a model registered in the world.
+//!
+//! Both traits are usually implemented with the `#[derive(DojoType)]` and
+//! `#[derive(DojoModel)]` macros:
+//!
+//! ```ignore
+//! #[derive(Component, DojoModel)]
+//! #[dojo(namespace = "di", name = "Position")]
+//! pub struct Position {
+//!     #[dojo(key)]
+//!     pub player: Felt,
+//!     pub x: u32,
+//!     pub y: u32,
+//! }
+//! ```
+
+use dojo_types::primitive::Primitive;
+use dojo_types::schema::{Enum, EnumOption, Struct, Ty};
+use starknet::core::types::{Felt, U256};
+
+use crate::error::DojoModelError;
+
+/// A Rust type that can be converted from and to a Dojo type.
+pub trait DojoType: Sized {
+    /// Converts the Dojo value into the Rust type.
+    fn from_ty(ty: &Ty) -> Result<Self, DojoModelError>;
+
+    /// Converts the Rust value into a Dojo value.
+    fn to_ty(&self) -> Ty;
+
+    /// Returns the Dojo type, without any value.
+    fn schema() -> Ty;
+}
+
+/// A Rust type mapped to a Dojo model.
+pub trait DojoModel: DojoType {
+    /// The namespace of the model.
+    const NAMESPACE: &'static str;
+    /// The name of the model, as declared in Cairo.
+    const NAME: &'static str;
+
+    /// Returns the tag of the model, `<namespace>-<name>`.
+    fn tag() -> String {
+        format!("{}-{}", Self::NAMESPACE, Self::NAME)
+    }
+
+    /// Converts the model received from Torii into the Rust type.
+    fn from_struct(s: &Struct) -> Result<Self, DojoModelError>;
+
+    /// Converts the Rust value into the model.
+    fn to_struct(&self) -> Struct;
+
+    /// Returns the serialized keys of the model.
+    fn keys(&self) -> Result<Vec<Felt>, DojoModelError>;
+}
+
+/// Returns the member of the struct with the given name.
+#[doc(hidden)]
+pub fn __struct_member<'a>(s: &'a Struct, member: &str) -> Result<&'a Ty, DojoModelError> {
+    s.children
+        .iter()
+        .find(|m| m.name == member)
+        .map(|m| &m.ty)
+        .ok_or_else(|| DojoModelError::MissingMember {
+            ty: s.name.clone(),
+            member: member.to_string(),
+        })
+}
+
+/// Serializes the value as Cairo calldata.
+#[doc(hidden)]
+pub fn __serialize<T: DojoType>(value: &T) -> Result<Vec<Felt>, DojoModelError> {
+    value
+        .to_ty()
+        .serialize()
+        .map_err(|e| DojoModelError::Serialization(e.to_string()))
+}
+
+macro_rules! impl_primitive {
+    ($ty:ty, $variant:ident) => {
+        impl DojoType for $ty {
+            fn from_ty(ty: &Ty) -> Result<Self, DojoModelError> {
+                match ty {
+                    Ty::Primitive(Primitive::$variant(Some(v))) => Ok(*v),
+                    _ => Err(DojoModelError::type_mismatch(stringify!($variant), ty)),
+                }
+            }
+
+            fn to_ty(&self) -> Ty {
+                Ty::Primitive(Primitive::$variant(Some(*self)))
+            }
+
+            fn schema() -> Ty {
+                Ty::Primitive(Primitive::$variant(None))
+            }
+        }
+    };
+}
+
+impl_primitive!(i8, I8);
+impl_primitive!(i16, I16);
+impl_primitive!(i32, I32);
+impl_primitive!(i64, I64);
+impl_primitive!(i128, I128);
+impl_primitive!(u8, U8);
+impl_primitive!(u16, U16);
+impl_primitive!(u32, U32);
+impl_primitive!(u64, U64);
+impl_primitive!(u128, U128);
+impl_primitive!(bool, Bool);
+
+/// Felts are accepted from any felt-like primitive (`felt252`, `ContractAddress`,
+/// `ClassHash` and `EthAddress`), and converted back to `felt252`.
+impl DojoType for Felt {
+    fn from_ty(ty: &Ty) -> Result<Self, DojoModelError> {
+        match ty {
+            Ty::Primitive(Primitive::Felt252(Some(v)))
+            | Ty::Primitive(Primitive::ContractAddress(Some(v)))
+            | Ty::Primitive(Primitive::ClassHash(Some(v)))
+            | Ty::Primitive(Primitive::EthAddress(Some(v))) => Ok(*v),
+            _ => Err(DojoModelError::type_mismatch("felt252", ty)),
+        }
+    }
+
+    fn to_ty(&self) -> Ty {
+        Ty::Primitive(Primitive::Felt252(Some(*self)))
+    }
+
+    fn schema() -> Ty {
+        Ty::Primitive(Primitive::Felt252(None))
+    }
+}
+
+/// Cairo `u256`, made of two `u128` words.
+impl DojoType for U256 {
+    fn from_ty(ty: &Ty) -> Result<Self, DojoModelError> {
+        let Ty::Primitive(primitive @ Primitive::U256(Some(_))) = ty else {
+            return Err(DojoModelError::type_mismatch("U256", ty));
+        };
+
+        // The words are read from the Cairo serialization of the value, `[low, high]`.
+        let felts = primitive
+            .serialize()
+            .map_err(|e| DojoModelError::Serialization(e.to_string()))?;
+
+        let word = |felt: Felt| {
+            u128::try_from(felt).map_err(|_| DojoModelError::type_mismatch("U256", ty))
+        };
+
+        match felts[..] {
+            [low, high] => Ok(U256::from_words(word(low)?, word(high)?)),
+            _ => Err(DojoModelError::type_mismatch("U256", ty)),
+        }
+    }
+
+    fn to_ty(&self) -> Ty {
+        let mut primitive = Primitive::U256(None);
+        primitive
+            .deserialize(&mut vec![self.low().into(), self.high().into()])
+            .expect("a u256 is made of two u128 words");
+
+        Ty::Primitive(primitive)
+    }
+
+    fn schema() -> Ty {
+        Ty::Primitive(Primitive::U256(None))
+    }
+}
+
+/// Cairo `ByteArray`.
+impl DojoType for String {
+    fn from_ty(ty: &Ty) -> Result<Self, DojoModelError> {
+        match ty {
+            Ty::ByteArray(s) => Ok(s.clone()),
+            _ => Err(DojoModelError::type_mismatch("ByteArray", ty)),
+        }
+    }
+
+    fn to_ty(&self) -> Ty {
+        Ty::ByteArray(self.clone())
+    }
+
+    fn schema() -> Ty {
+        Ty::ByteArray(String::new())
+    }
+}
+
+/// Cairo `Array<T>`.
+impl<T: DojoType> DojoType for Vec<T> {
+    fn from_ty(ty: &Ty) -> Result<Self, DojoModelError> {
+        match ty {
+            Ty::Array(items) => items.iter().map(T::from_ty).collect(),
+            _ => Err(DojoModelError::type_mismatch("Array", ty)),
+        }
+    }
+
+    fn to_ty(&self) -> Ty {
+        Ty::Array(self.iter().map(T::to_ty).collect())
+    }
+
+    fn schema() -> Ty {
+        Ty::Array(vec![T::schema()])
+    }
+}
+
+/// Cairo `Option<T>`.
+impl<T: DojoType> DojoType for Option<T> {
+    fn from_ty(ty: &Ty) -> Result<Self, DojoModelError> {
+        let Ty::Enum(e) = ty else {
+            return Err(DojoModelError::type_mismatch("Option", ty));
+        };
+
+        let option = e
+            .option
+            .and_then(|o| e.options.get(o as usize))
+            .ok_or_else(|| DojoModelError::MissingVariant { ty: e.name.clone() })?;
+
+        match option.name.as_str() {
+            "Some" => Ok(Some(T::from_ty(&option.ty)?)),
+            "None" => Ok(None),
+            variant => Err(DojoModelError::UnknownVariant {
+                ty: e.name.clone(),
+                variant: variant.to_string(),
+            }),
+        }
+    }
+
+    fn to_ty(&self) -> Ty {
+        match self {
+            Some(v) => option_ty(Some(0), v.to_ty()),
+            None => option_ty(Some(1), T::schema()),
+        }
+    }
+
+    fn schema() -> Ty {
+        option_ty(None, T::schema())
+    }
+}
+
+/// Builds the Cairo `Option` enum, with the given selected option.
+fn option_ty(option: Option<u8>, some: Ty) -> Ty {
+    Ty::Enum(Enum {
+        name: "Option".to_string(),
+        option,
+        options: vec![
+            EnumOption {
+                name: "Some".to_string(),
+                ty: some,
+            },
+            EnumOption {
+                name: "None".to_string(),
+                ty: Ty::Tuple(vec![]),
+            },
+        ],
+    })
+}
+
+/// Cairo unit type `()`.
+impl DojoType for () {
+    fn from_ty(ty: &Ty) -> Result<Self, DojoModelError> {
+        match ty {
+            Ty::Tuple(items) if items.is_empty() => Ok(()),
+            _ => Err(DojoModelError::type_mismatch("()", ty)),
+        }
+    }
+
+    fn to_ty(&self) -> Ty {
+        Self::schema()
+    }
+
+    fn schema() -> Ty {
+        Ty::Tuple(vec![])
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident : $idx:tt),+) => {
+        impl<$($name: DojoType),+> DojoType for ($($name,)+) {
+            fn from_ty(ty: &Ty) -> Result<Self, DojoModelError> {
+                match ty {
+                    Ty::Tuple(items) if items.len() == [$($idx),+].len() => {
+                        Ok(($($name::from_ty(&items[$idx])?,)+))
+                    }
+                    _ => Err(DojoModelError::type_mismatch("Tuple", ty)),
+                }
+            }
+
+            fn to_ty(&self) -> Ty {
+                Ty::Tuple(vec![$(self.$idx.to_ty()),+])
+            }
+
+            fn schema() -> Ty {
+                Ty::Tuple(vec![$($name::schema()),+])
+            }
+        }
+    };
+}
+
+impl_tuple!(A: 0);
+impl_tuple!(A: 0, B: 1);
+impl_tuple!(A: 0, B: 1, C: 2);
+impl_tuple!(A: 0, B: 1, C: 2, D: 3);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::DojoModelError;
+    use crate::{DojoModel, DojoType};
+
+    #[derive(Debug, Clone, Copy, PartialEq, DojoType)]
+    enum Direction {
+        Left,
+        Right,
+        Up(u32),
+    }
+
+    #[derive(Debug, Clone, PartialEq, DojoType)]
+    struct Vec2 {
+        x: u32,
+        y: u32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, DojoModel)]
+    #[dojo(namespace = "di", name = "Moves")]
+    struct Moves {
+        #[dojo(key)]
+        player: Felt,
+        position: Vec2,
+        last_direction: Option<Direction>,
+        r#type: u8,
+    }
+
+    fn moves() -> Moves {
+        Moves {
+            player: Felt::from(0x42_u64),
+            position: Vec2 { x: 3, y: 4 },
+            last_direction: Some(Direction::Up(2)),
+            r#type: 1,
+        }
+    }
+
+    #[test]
+    fn model_round_trip() {
+        let s = moves().to_struct();
+
+        assert_eq!(s.name, "di-Moves");
+        let members: Vec<_> = s.children.iter().map(|m| (m.name.as_str(), m.key)).collect();
+        assert_eq!(
+            members,
+            [
+                ("player", true),
+                ("position", false),
+                ("last_direction", false),
+                ("type", false),
+            ]
+        );
+
+        assert_eq!(Moves::from_struct(&s).unwrap(), moves());
+        assert_eq!(moves().keys().unwrap(), [Felt::from(0x42_u64)]);
+    }
+
+    #[test]
+    fn model_unexpected_name() {
+        let mut s = moves().to_struct();
+        s.name = "di-Position".to_string();
+
+        assert!(matches!(
+            Moves::from_struct(&s),
+            Err(DojoModelError::UnexpectedModel { .. })
+        ));
+    }
+
+    #[test]
+    fn model_missing_member() {
+        let mut s = moves().to_struct();
+        s.children.retain(|m| m.name != "position");
+
+        assert!(matches!(
+            Moves::from_struct(&s),
+            Err(DojoModelError::MissingMember { member, .. }) if member == "position"
+        ));
+    }
+
+    #[test]
+    fn enum_round_trip() {
+        for direction in [Direction::Left, Direction::Right, Direction::Up(7)] {
+            assert_eq!(Direction::from_ty(&direction.to_ty()).unwrap(), direction);
+        }
+
+        let Ty::Enum(e) = Direction::Up(7).to_ty() else {
+            panic!("expected an enum");
+        };
+        assert_eq!(e.name, "Direction");
+        assert_eq!(e.option, Some(2));
+        assert_eq!(e.options[2].ty, 7_u32.to_ty());
+    }
+
+    #[test]
+    fn enum_unknown_variant() {
+        let Ty::Enum(mut e) = Direction::Left.to_ty() else {
+            panic!("expected an enum");
+        };
+        e.options[0].name = "Down".to_string();
+
+        assert!(matches!(
+            Direction::from_ty(&Ty::Enum(e)),
+            Err(DojoModelError::UnknownVariant { variant, .. }) if variant == "Down"
+        ));
+    }
+
+    #[test]
+    fn option_serialization() {
+        let some = Some(5_u32).to_ty();
+        let none = None::<u32>.to_ty();
+
+        assert_eq!(__serialize(&Some(5_u32)).unwrap(), [Felt::ZERO, Felt::from(5_u64)]);
+        assert_eq!(Option::<u32>::from_ty(&some).unwrap(), Some(5));
+        assert_eq!(Option::<u32>::from_ty(&none).unwrap(), None);
+    }
+
+    #[test]
+    fn u256_round_trip() {
+        let value = U256::from_words(u128::MAX, 2);
+
+        assert_eq!(U256::from_ty(&value.to_ty()).unwrap(), value);
+        assert_eq!(
+            __serialize(&value).unwrap(),
+            [Felt::from(u128::MAX), Felt::TWO]
+        );
+        assert!(U256::from_ty(&1_u128.to_ty()).is_err());
+    }
+}
@@ -5,37 +5,196 @@
 //!
 //! This resources aims at providing a single point of access to interact with Dojo.
 
+use crate::account::{DojoAccount, SessionAccount, SessionConfig};
+use crate::call::{DojoCallFailed, DojoCallId, DojoCallResult, execute_call};
+use crate::error::DojoError;
+use crate::manifest::DojoManifest;
+use crate::model::DojoType;
+use crate::reconnect::{DojoConnectionKind, DojoReconnected, ReconnectPolicy, Reconnection};
+use crate::signer::{DojoSigner, DojoWallet, load_keystore};
+use crate::state::{StarknetState, ToriiState, sync_connection_states};
+use crate::sync::{DojoEntityMap, DojoSet, despawn_deleted_entities, on_dojo_entity_removed};
+use crate::system::DojoSystem;
+use crate::tx::{
+    DojoFeeEstimateFailed, DojoFeeEstimateId, DojoFeeEstimated, DojoTxAccepted, DojoTxFailed,
+    DojoTxFeeEstimated, DojoTxId, DojoTxReverted, DojoTxSubmitted, NonceTracker, TxBatch,
+    TxBatching, TxConfig, TxFeeOptions, TxTurns, TxUpdate, TxUpdateSender, estimate_calls_fee,
+    execute_tx,
+};
+use bevy::ecs::system::SystemParam;
 use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
 use dojo_types::schema::Struct;
 use futures::StreamExt;
-use starknet::accounts::single_owner::SignError;
-use starknet::accounts::{Account, AccountError, ExecutionEncoding, SingleOwnerAccount};
-use starknet::core::types::{BlockId, BlockTag, Call, InvokeTransactionResult};
+use starknet::accounts::{ExecutionEncoding, SingleOwnerAccount};
+use starknet::core::types::{BlockId, BlockTag, Call, FeeEstimate};
 use starknet::providers::jsonrpc::HttpTransport;
 use starknet::providers::{JsonRpcClient, Provider};
-use starknet::signers::local_wallet::SignError as LocalWalletSignError;
-use starknet::signers::{LocalWallet, SigningKey};
 use starknet::{core::types::Felt, providers::AnyProvider};
 use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
 use tokio::sync::Mutex;
-use tokio::sync::mpsc::{Receiver, Sender, channel};
-use tokio::task::JoinHandle;
+use tokio::sync::mpsc::{Receiver, Sender, UnboundedReceiver, channel, unbounded_channel};
+use tokio::task::{JoinError, JoinHandle};
 use torii_grpc_client::WorldClient;
 use torii_grpc_client::types::proto::world::RetrieveEntitiesResponse;
 use torii_grpc_client::types::{Clause, Query as ToriiQuery};
 use url::Url;
 
 /// The Dojo plugin to connect Bevy to Torii and Starknet.
-pub struct DojoPlugin;
+///
+/// The plugin registers the `DojoResource` and `TokioRuntime` resources,
+/// and connects to Torii and Starknet on `Startup` if `auto_connect` is set.
+///
+/// The `StatesPlugin` is added if missing, for the connection states. The `DefaultPlugins`
+/// must then be added before the Dojo plugin, since they also contain the `StatesPlugin`.
+#[derive(Clone)]
+pub struct DojoPlugin {
+    pub torii_url: String,
+    pub rpc_url: String,
+    pub world_address: Felt,
+    /// Account to connect to Starknet with, only Torii is connected if `None`.
+    pub account: Option<AccountSource>,
+    /// Connects to Torii and Starknet on `Startup`.
+    pub auto_connect: bool,
+    /// Policy to reconnect to Torii and Starknet, no reconnection is attempted if `None`.
+    pub reconnect: Option<ReconnectPolicy>,
+    pub budget: PollBudget,
+    pub tx_config: TxConfig,
+    /// If set, the Starknet connection fails when the RPC reports another chain id.
+    pub expected_chain_id: Option<Felt>,
+    /// The block against which the calls queued with `queue_call` are executed.
+    pub call_block_id: BlockId,
+    /// The manifest of the world, made available in the `DojoResource`
+    /// and as the `DojoManifest` resource.
+    pub manifest: Option<DojoManifest>,
+}
+
+impl Default for DojoPlugin {
+    fn default() -> Self {
+        Self {
+            torii_url: "http://localhost:8080".to_string(),
+            rpc_url: "http://0.0.0.0:5050".to_string(),
+            world_address: Felt::ZERO,
+            account: None,
+            auto_connect: false,
+            reconnect: None,
+            budget: PollBudget::default(),
+            tx_config: TxConfig::default(),
+            expected_chain_id: None,
+            call_block_id: BlockId::Tag(BlockTag::Pending),
+            manifest: None,
+        }
+    }
+}
+
+impl DojoPlugin {
+    /// Uses the world address of the manifest, and makes the manifest
+    /// available in the `DojoResource` and as the `DojoManifest` resource.
+    pub fn with_manifest(mut self, manifest: DojoManifest) -> Self {
+        self.world_address = manifest.world_address();
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Connects to Torii and to the configured Starknet account, if any.
+    pub fn connect(&self, tokio: &TokioRuntime, dojo: &mut DojoResource) {
+        dojo.connect_torii(tokio, self.torii_url.clone(), self.world_address);
+
+        match self.account.clone() {
+            Some(AccountSource::PrivateKey {
+                address,
+                private_key,
+            }) => dojo.connect_account(tokio, self.rpc_url.clone(), address, private_key),
+            Some(AccountSource::Signer { address, signer }) => {
+                dojo.connect_account_with_signer(tokio, self.rpc_url.clone(), address, signer)
+            }
+            Some(AccountSource::Keystore {
+                address,
+                path,
+                password,
+            }) => {
+                dojo.connect_keystore_account(tokio, self.rpc_url.clone(), address, path, password)
+            }
+            Some(AccountSource::Predeployed(idx)) => {
+                dojo.connect_predeployed_account(tokio, self.rpc_url.clone(), idx)
+            }
+            Some(AccountSource::Session(config)) => {
+                dojo.connect_session(tokio, self.rpc_url.clone(), config)
+            }
+            None => {}
+        }
+    }
+}
 
 impl Plugin for DojoPlugin {
     fn build(&self, app: &mut App) {
+        if let Some(policy) = self.reconnect {
+            app.insert_resource(policy);
+        }
+
+        if let Some(manifest) = &self.manifest {
+            app.insert_resource(manifest.clone());
+        }
+
+        let mut dojo = DojoResource {
+            budget: self.budget,
+            manifest: self.manifest.clone(),
+            ..Default::default()
+        };
+        dojo.sn.tx_config = self.tx_config;
+        dojo.sn.expected_chain_id = self.expected_chain_id;
+        dojo.sn.call_block_id = self.call_block_id;
+
+        app.insert_resource(dojo);
+        app.init_resource::<TokioRuntime>();
+
+        if self.auto_connect {
+            let config = self.clone();
+            app.add_systems(
+                Startup,
+                move |tokio: Res<TokioRuntime>, mut dojo: ResMut<DojoResource>| {
+                    config.connect(&tokio, &mut dojo);
+                },
+            );
+        }
+
         app.add_event::<DojoInitializedEvent>();
         app.add_event::<DojoEntityUpdated>();
-        app.add_systems(Update, (check_torii_task, check_sn_task));
+        app.add_event::<DojoEntityDeleted>();
+        app.add_event::<DojoConnectionFailed>();
+        app.add_event::<DojoToriiConnectionFailed>();
+        app.add_event::<DojoReconnected>();
+        app.add_event::<DojoTxFeeEstimated>();
+        app.add_event::<DojoTxSubmitted>();
+        app.add_event::<DojoTxAccepted>();
+        app.add_event::<DojoTxReverted>();
+        app.add_event::<DojoTxFailed>();
+        app.add_event::<DojoCallResult>();
+        app.add_event::<DojoCallFailed>();
+        app.add_event::<DojoFeeEstimated>();
+        app.add_event::<DojoFeeEstimateFailed>();
+        // The states require the `StatesPlugin`, part of the `DefaultPlugins`
+        // but not of the `MinimalPlugins`.
+        if !app.is_plugin_added::<StatesPlugin>() {
+            app.add_plugins(StatesPlugin);
+        }
+        app.init_state::<ToriiState>();
+        app.init_state::<StarknetState>();
+        app.init_resource::<DojoEntityMap>();
+        app.add_observer(on_dojo_entity_removed);
+        app.configure_sets(Update, (DojoSet::Poll, DojoSet::SyncModels).chain());
+        app.add_systems(
+            Update,
+            ((check_torii_task, check_sn_task), sync_connection_states)
+                .chain()
+                .in_set(DojoSet::Poll),
+        );
+        app.add_systems(Update, despawn_deleted_entities.in_set(DojoSet::SyncModels));
     }
 }
 
@@ -45,12 +204,50 @@ pub struct DojoInitializedEvent;
 
 /// This event is emitted everytime we receive an entity update from Torii.
 /// This could be done by fetching entities, or by subscribing to entities updates.
+///
+/// Deleted models are not part of the update, they are reported by `DojoEntityDeleted`.
+/// An update is not emitted if the entity or the model is deleted later in the same frame.
 #[derive(Event, Debug)]
 pub struct DojoEntityUpdated {
     pub entity_id: Felt,
     pub models: Vec<Struct>,
 }
 
+/// This event is emitted when models of an entity have been deleted from the world.
+///
+/// Torii reports a deleted model without any member, and an entity without
+/// any model once all its models are deleted, in which case `models_removed` is empty.
+#[derive(Event, Debug)]
+pub struct DojoEntityDeleted {
+    pub entity_id: Felt,
+    /// The tags of the deleted models, empty if the whole entity has been deleted.
+    pub models_removed: Vec<String>,
+}
+
+impl DojoEntityDeleted {
+    /// Returns true if the whole entity has been deleted.
+    pub fn is_entity_deleted(&self) -> bool {
+        self.models_removed.is_empty()
+    }
+
+    /// Returns true if the model with the given tag has been deleted.
+    pub fn is_model_deleted(&self, tag: &str) -> bool {
+        self.is_entity_deleted() || self.models_removed.iter().any(|m| m == tag)
+    }
+}
+
+/// This event is emitted when the connection to Starknet failed.
+#[derive(Event, Debug)]
+pub struct DojoConnectionFailed {
+    pub error: DojoError,
+}
+
+/// This event is emitted when the connection to Torii failed.
+#[derive(Event, Debug)]
+pub struct DojoToriiConnectionFailed {
+    pub error: DojoError,
+}
+
 /// Resource to store the Tokio runtime, required by starknet-rs.
 ///
 /// This resource will never be used as mut, this is why it is not embedded in the Dojo resource.
@@ -67,26 +264,233 @@ impl Default for TokioRuntime {
     }
 }
 
+/// The account to connect to Starknet with.
+#[derive(Clone)]
+pub enum AccountSource {
+    /// An account controlled by the given private key.
+    PrivateKey { address: Felt, private_key: Felt },
+    /// An account signed by the given signer.
+    Signer {
+        address: Felt,
+        signer: Arc<dyn DojoSigner>,
+    },
+    /// An account controlled by the private key of an encrypted JSON keystore.
+    ///
+    /// The password is kept to load the keystore again on reconnection.
+    Keystore {
+        address: Felt,
+        path: PathBuf,
+        password: String,
+    },
+    /// A predeployed account of a Starknet node started in dev mode, by index.
+    Predeployed(usize),
+    /// A session of a player account.
+    Session(SessionConfig),
+}
+
 /// Starknet connection state.
-#[derive(Default)]
 pub struct StarknetConnection {
-    pub connecting_task: Option<JoinHandle<Arc<SingleOwnerAccount<AnyProvider, LocalWallet>>>>,
-    pub account: Option<Arc<SingleOwnerAccount<AnyProvider, LocalWallet>>>,
-    pub pending_txs: VecDeque<
-        JoinHandle<Result<InvokeTransactionResult, AccountError<SignError<LocalWalletSignError>>>>,
-    >,
+    pub status: ConnectionStatus,
+    /// The error of the last failed connection attempt.
+    pub last_error: Option<String>,
+    pub connecting_task: Option<JoinHandle<Result<Arc<DojoAccount>, DojoError>>>,
+    pub account: Option<Arc<DojoAccount>>,
+    pub pending_txs: VecDeque<JoinHandle<()>>,
+    pub pending_calls: VecDeque<JoinHandle<(DojoCallId, Result<Vec<Felt>, String>)>>,
+    pub pending_fee_estimates:
+        VecDeque<JoinHandle<(DojoFeeEstimateId, Result<FeeEstimate, String>)>>,
+    pub tx_config: TxConfig,
+    /// If set, the connection fails when the RPC reports another chain id.
+    pub expected_chain_id: Option<Felt>,
+    /// The block against which the calls are executed.
+    pub call_block_id: BlockId,
+    next_tx_id: u64,
+    next_call_id: u64,
+    next_fee_estimate_id: u64,
+    nonces: NonceTracker,
+    tx_turns: TxTurns,
+    tx_batch: TxBatch,
+    tx_update_sender: TxUpdateSender,
+    tx_update_receiver: UnboundedReceiver<(DojoTxId, TxUpdate)>,
+    source: Option<(String, AccountSource)>,
+    reconnection: Option<Reconnection>,
+}
+
+impl Default for StarknetConnection {
+    fn default() -> Self {
+        let (tx_update_sender, tx_update_receiver) = unbounded_channel();
+
+        Self {
+            status: ConnectionStatus::Disconnected,
+            last_error: None,
+            connecting_task: None,
+            account: None,
+            pending_txs: VecDeque::new(),
+            pending_calls: VecDeque::new(),
+            pending_fee_estimates: VecDeque::new(),
+            tx_config: TxConfig::default(),
+            expected_chain_id: None,
+            call_block_id: BlockId::Tag(BlockTag::Pending),
+            next_tx_id: 0,
+            next_call_id: 0,
+            next_fee_estimate_id: 0,
+            nonces: NonceTracker::default(),
+            tx_turns: TxTurns::default(),
+            tx_batch: TxBatch::default(),
+            tx_update_sender,
+            tx_update_receiver,
+            source: None,
+            reconnection: None,
+        }
+    }
+}
+
+impl StarknetConnection {
+    /// Attributes the id of the next queued transaction.
+    fn next_tx_id(&mut self) -> DojoTxId {
+        let id = DojoTxId(self.next_tx_id);
+        self.next_tx_id += 1;
+        id
+    }
+
+    /// Spawns the task connecting to the account from the last used source.
+    fn spawn_connect(&mut self, tokio: &TokioRuntime) {
+        let Some((rpc_url, source)) = self.source.clone() else {
+            return;
+        };
+
+        let expected_chain_id = self.expected_chain_id;
+        let task = tokio.runtime.spawn(async move {
+            connect_account_source(rpc_url, source, expected_chain_id)
+                .await
+                .map(Arc::new)
+        });
+
+        self.connecting_task = Some(task);
+        self.status = ConnectionStatus::Connecting;
+    }
+}
+
+/// Status of a connection, to be displayed by the UI.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ConnectionStatus {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+    Failed,
 }
 
 /// Torii connection state.
 #[derive(Default)]
 pub struct ToriiConnection {
+    pub status: ConnectionStatus,
+    /// The error of the last failed connection attempt.
+    pub last_error: Option<String>,
     pub init_task: Option<JoinHandle<Result<WorldClient, torii_grpc_client::Error>>>,
     pub client: Option<Arc<Mutex<WorldClient>>>,
     pub pending_retrieve_entities:
         VecDeque<JoinHandle<Result<RetrieveEntitiesResponse, torii_grpc_client::Error>>>,
-    pub subscriptions: Arc<Mutex<HashMap<String, JoinHandle<()>>>>,
-    pub subscription_sender: Option<Arc<Mutex<Sender<(Felt, Vec<Struct>)>>>>,
-    pub subscription_receiver: Option<Arc<Mutex<Receiver<(Felt, Vec<Struct>)>>>>,
+    pub subscriptions: HashMap<String, ToriiSubscription>,
+    pub subscription_sender: Option<Arc<Mutex<Sender<SubscriptionUpdate>>>>,
+    pub subscription_receiver: Option<Arc<Mutex<Receiver<SubscriptionUpdate>>>>,
+    endpoint: Option<(String, Felt)>,
+    reconnection: Option<Reconnection>,
+    /// The attempt and time of the last successful reconnection.
+    recovered: Option<(u32, Instant)>,
+    connected_once: bool,
+}
+
+/// A Torii entities subscription.
+///
+/// The clause is kept to register the subscription again on reconnection.
+pub struct ToriiSubscription {
+    pub clause: Option<Clause>,
+    /// The task streaming the updates, `None` while waiting for a reconnection.
+    ///
+    /// The task fails if Torii rejected the subscription or if the stream failed,
+    /// and ends successfully when the stream is closed, which means the connection
+    /// has been lost.
+    pub task: Option<JoinHandle<Result<(), torii_grpc_client::Error>>>,
+    /// The id attributed by Torii, once the subscription is registered.
+    pub subscription_id: Option<u64>,
+}
+
+/// Message sent by the subscription tasks to the plugin.
+#[derive(Debug)]
+pub enum SubscriptionUpdate {
+    /// Torii registered the subscription with the given id.
+    Subscribed { id: String, subscription_id: u64 },
+    /// An entity has been updated.
+    Entity {
+        entity_id: Felt,
+        models: Vec<Struct>,
+    },
+}
+
+impl ToriiConnection {
+    /// Spawns the task creating the Torii client from the last used endpoint.
+    fn spawn_client(&mut self, tokio: &TokioRuntime) {
+        let Some((torii_url, world_address)) = self.endpoint.clone() else {
+            return;
+        };
+
+        let task = tokio
+            .runtime
+            .spawn(async move { WorldClient::new(torii_url, world_address).await });
+        self.init_task = Some(task);
+        self.status = ConnectionStatus::Connecting;
+    }
+
+    /// Registers all the subscriptions again on the current client.
+    fn resubscribe(&mut self, tokio: &TokioRuntime) {
+        let Some(client) = self.client.clone() else {
+            return;
+        };
+
+        for (id, subscription) in self.subscriptions.iter_mut() {
+            if let Some(task) = subscription.task.take() {
+                task.abort();
+            }
+            subscription.subscription_id = None;
+            subscription.task = Some(spawn_subscription(
+                tokio,
+                client.clone(),
+                self.subscription_sender.clone(),
+                id.clone(),
+                subscription.clause.clone(),
+            ));
+        }
+    }
+}
+
+/// Limits how much work the Dojo systems do in a single frame.
+///
+/// Every finished task or subscription update consumes one unit of the budget.
+/// When no limit is set (the default), everything that is ready is processed
+/// in the current frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PollBudget {
+    /// Maximum number of results processed per frame and per system.
+    pub max_updates: Option<usize>,
+    /// Maximum time spent processing results per frame and per system.
+    pub max_duration: Option<Duration>,
+}
+
+/// Number of results still waiting to be processed after the last frame.
+///
+/// Updated every frame by the Dojo systems, this is useful to tune the
+/// [`PollBudget`] or to display a loading indicator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DojoBacklog {
+    /// Retrieve entities queries not yet processed, finished or not.
+    pub retrieve_entities: usize,
+    /// Subscription updates waiting in the channel.
+    pub subscription_updates: usize,
+    /// Transactions not yet processed, finished or not, including the batched ones.
+    pub transactions: usize,
+    /// Calls and fee estimations not yet processed, finished or not.
+    pub calls: usize,
 }
 
 /// Dojo resource that embeds Starknet and Torii connection.
@@ -94,6 +498,9 @@ pub struct ToriiConnection {
 pub struct DojoResource {
     pub sn: StarknetConnection,
     pub torii: ToriiConnection,
+    pub budget: PollBudget,
+    pub backlog: DojoBacklog,
+    pub manifest: Option<DojoManifest>,
 }
 
 impl DojoResource {
@@ -106,10 +513,11 @@ impl DojoResource {
     /// `check_sn_task`.
     pub fn connect_torii(&mut self, tokio: &TokioRuntime, torii_url: String, world_address: Felt) {
         info!("Connecting to Torii.");
-        let task = tokio
-            .runtime
-            .spawn(async move { WorldClient::new(torii_url, world_address).await });
-        self.torii.init_task = Some(task);
+        self.torii.endpoint = Some((torii_url, world_address));
+        self.torii.reconnection = None;
+        self.torii.recovered = None;
+        self.torii.connected_once = false;
+        self.torii.spawn_client(tokio);
 
         let (sender, receiver) = channel(100);
         self.torii.subscription_sender = Some(Arc::new(Mutex::new(sender)));
@@ -125,13 +533,58 @@ impl DojoResource {
         private_key: Felt,
     ) {
         info!("Connecting to Starknet.");
-        let task = tokio
-            .runtime
-            .spawn(async move { connect_to_starknet(rpc_url, account_addr, private_key).await });
+        let source = AccountSource::PrivateKey {
+            address: account_addr,
+            private_key,
+        };
 
-        self.sn.connecting_task = Some(task);
+        self.sn.source = Some((rpc_url, source));
+        self.sn.reconnection = None;
+        self.sn.spawn_connect(tokio);
     }
 
+    /// Connects to a Starknet account signed by the given signer.
+    pub fn connect_account_with_signer(
+        &mut self,
+        tokio: &TokioRuntime,
+        rpc_url: String,
+        account_addr: Felt,
+        signer: Arc<dyn DojoSigner>,
+    ) {
+        info!("Connecting to Starknet.");
+        let source = AccountSource::Signer {
+            address: account_addr,
+            signer,
+        };
+
+        self.sn.source = Some((rpc_url, source));
+        self.sn.reconnection = None;
+        self.sn.spawn_connect(tokio);
+    }
+
+    /// Connects to a Starknet account controlled by the private key of an encrypted
+    /// JSON keystore. The keystore is decrypted in the connection task.
+    pub fn connect_keystore_account(
+        &mut self,
+        tokio: &TokioRuntime,
+        rpc_url: String,
+        account_addr: Felt,
+        path: PathBuf,
+        password: String,
+    ) {
+        info!("Connecting to Starknet (keystore).");
+        let source = AccountSource::Keystore {
+            address: account_addr,
+            path,
+            password,
+        };
+
+        self.sn.source = Some((rpc_url, source));
+        self.sn.reconnection = None;
+        self.sn.spawn_connect(tokio);
+    }
+
+    /// Connects to a predeployed account of a Starknet node started in dev mode.
     pub fn connect_predeployed_account(
         &mut self,
         tokio: &TokioRuntime,
@@ -139,11 +592,25 @@ impl DojoResource {
         account_idx: usize,
     ) {
         info!("Connecting to Starknet (predeployed).");
-        let task = tokio
-            .runtime
-            .spawn(async move { connect_predeployed_account(rpc_url, account_idx).await });
+        self.sn.source = Some((rpc_url, AccountSource::Predeployed(account_idx)));
+        self.sn.reconnection = None;
+        self.sn.spawn_connect(tokio);
+    }
 
-        self.sn.connecting_task = Some(task);
+    /// Connects to the session of a player account.
+    ///
+    /// The transactions are sent by the sender of the session, as outside
+    /// executions of the player account signed by the session key.
+    pub fn connect_session(
+        &mut self,
+        tokio: &TokioRuntime,
+        rpc_url: String,
+        config: SessionConfig,
+    ) {
+        info!("Connecting to Starknet (session).");
+        self.sn.source = Some((rpc_url, AccountSource::Session(config)));
+        self.sn.reconnection = None;
+        self.sn.spawn_connect(tokio);
     }
 
     /// Queues a transaction to be sent to the Starknet account.
@@ -152,17 +619,175 @@ impl DojoResource {
     /// The Dojo plugin will then register a system to check the state of the
     /// transaction and send it to the Starknet account if needed in an asynchronous
     /// way (`check_sn_task`).
-    pub fn queue_tx(&mut self, tokio: &TokioRuntime, calls: Vec<Call>) {
-        if let Some(account) = self.sn.account.clone() {
-            let task = tokio.runtime.spawn(async move {
-                let tx = account.execute_v3(calls);
-                tx.send().await
-            });
+    ///
+    /// The returned id is carried by the `DojoTxSubmitted`, `DojoTxAccepted`,
+    /// `DojoTxReverted` and `DojoTxFailed` events emitted for this transaction.
+    ///
+    /// The transaction is sent with the fee options of the `TxConfig`.
+    /// If `TxConfig::batching` is enabled, the transaction is added to the batch
+    /// sent as a single multicall once due.
+    pub fn queue_tx(&mut self, tokio: &TokioRuntime, calls: Vec<Call>) -> DojoTxId {
+        if self.sn.tx_config.batching == TxBatching::Disabled || self.sn.account.is_none() {
+            let fee = self.sn.tx_config.fee;
+            return self.queue_tx_with(tokio, calls, fee);
+        }
 
-            self.sn.pending_txs.push_back(task);
-        } else {
-            warn!("No Starknet account initialized, skipping transaction.");
+        let id = self.sn.next_tx_id();
+        self.sn.tx_batch.push(id, calls);
+        id
+    }
+
+    /// Queues a transaction, sent with the given fee options (see `queue_tx`).
+    ///
+    /// The transaction is never batched, since the fee options apply to the whole multicall.
+    pub fn queue_tx_with(
+        &mut self,
+        tokio: &TokioRuntime,
+        calls: Vec<Call>,
+        fee: TxFeeOptions,
+    ) -> DojoTxId {
+        let config = TxConfig {
+            fee,
+            ..self.sn.tx_config
+        };
+
+        let id = self.sn.next_tx_id();
+        self.spawn_tx_task(tokio, vec![id], calls, config);
+        id
+    }
+
+    /// Sends the batched transactions as a single multicall, without waiting
+    /// for the batch to be due.
+    pub fn flush_tx_batch(&mut self, tokio: &TokioRuntime) {
+        let (ids, calls) = self.sn.tx_batch.take();
+        if ids.is_empty() {
+            return;
         }
+
+        debug!("Sending {} batched transactions as a multicall.", ids.len());
+        let config = self.sn.tx_config;
+        self.spawn_tx_task(tokio, ids, calls, config);
+    }
+
+    /// Queues the fee estimation of the calls, without sending any transaction.
+    ///
+    /// The returned id is carried by the `DojoFeeEstimated` event, or by the
+    /// `DojoFeeEstimateFailed` event if the estimation failed.
+    pub fn queue_fee_estimate(
+        &mut self,
+        tokio: &TokioRuntime,
+        calls: Vec<Call>,
+    ) -> DojoFeeEstimateId {
+        let id = DojoFeeEstimateId(self.sn.next_fee_estimate_id);
+        self.sn.next_fee_estimate_id += 1;
+
+        let fee = self.sn.tx_config.fee;
+        let account = self.sn.account.clone();
+
+        let task = tokio.runtime.spawn(async move {
+            let result = match account {
+                Some(account) => estimate_calls_fee(account.as_ref(), calls, fee).await,
+                None => Err("No Starknet account initialized".to_string()),
+            };
+
+            (id, result)
+        });
+
+        self.sn.pending_fee_estimates.push_back(task);
+        id
+    }
+
+    /// Spawns the execution of the calls, reported to the given transaction ids,
+    /// or reports the transactions as failed if no account is connected.
+    fn spawn_tx_task(
+        &mut self,
+        tokio: &TokioRuntime,
+        ids: Vec<DojoTxId>,
+        calls: Vec<Call>,
+        config: TxConfig,
+    ) {
+        let updates = self.sn.tx_update_sender.clone();
+
+        let Some(account) = self.sn.account.clone() else {
+            warn!("No Starknet account initialized, skipping transaction.");
+            for id in ids {
+                let _ = updates.send((
+                    id,
+                    TxUpdate::Failed {
+                        transaction_hash: None,
+                        error: "No Starknet account initialized".to_string(),
+                    },
+                ));
+            }
+            return;
+        };
+
+        let nonces = self.sn.nonces.clone();
+        let turn = self.sn.tx_turns.turn();
+        let task = tokio.runtime.spawn(async move {
+            execute_tx(account.as_ref(), ids, calls, config, nonces, turn, updates).await
+        });
+        self.sn.pending_txs.push_back(task);
+    }
+
+    /// Queues a read-only call, executed with `starknet_call` against the configured block.
+    ///
+    /// The calls are executed with the provider of the account if connected, or
+    /// with the RPC of the last account connection otherwise.
+    ///
+    /// The returned id is carried by the `DojoCallResult` and `DojoCallFailed`
+    /// events emitted for this call.
+    pub fn queue_call(&mut self, tokio: &TokioRuntime, call: Call) -> DojoCallId {
+        let id = DojoCallId(self.sn.next_call_id);
+        self.sn.next_call_id += 1;
+
+        let block_id = self.sn.call_block_id;
+        let account = self.sn.account.clone();
+        let rpc_url = self.sn.source.as_ref().map(|(url, _)| url.clone());
+
+        let task = tokio.runtime.spawn(async move {
+            let result = match (account, rpc_url) {
+                (Some(account), _) => execute_call(account.provider(), call, block_id).await,
+                (None, Some(rpc_url)) => match rpc_provider(&rpc_url) {
+                    Ok(provider) => execute_call(&provider, call, block_id).await,
+                    Err(e) => Err(e.to_string()),
+                },
+                (None, None) => Err("No Starknet connection initialized".to_string()),
+            };
+
+            (id, result)
+        });
+
+        self.sn.pending_calls.push_back(task);
+        id
+    }
+
+    /// Queues the call of a system, identified by the tag of its contract and its name.
+    ///
+    /// The arguments are given as a tuple, `()` if the system has none.
+    /// See `queue_system` for the resolution of the call.
+    pub fn call_system<A: DojoType>(
+        &mut self,
+        tokio: &TokioRuntime,
+        contract_tag: &str,
+        system: &str,
+        args: A,
+    ) -> Result<DojoTxId, DojoError> {
+        self.queue_system(tokio, DojoSystem::new(contract_tag, system, args))
+    }
+
+    /// Queues the call of a system as a transaction (see `queue_tx`).
+    ///
+    /// The address of the contract is resolved from the manifest, and the number
+    /// of arguments is checked against its ABI before queuing the transaction.
+    pub fn queue_system(
+        &mut self,
+        tokio: &TokioRuntime,
+        system: DojoSystem,
+    ) -> Result<DojoTxId, DojoError> {
+        let manifest = self.manifest.as_ref().ok_or(DojoError::ManifestMissing)?;
+        let call = system.to_call(manifest)?;
+        Ok(self.queue_tx(tokio, vec![call]))
     }
 
     /// Queues a retrieve entities query to be sent to Torii.
@@ -193,128 +818,731 @@ impl DojoResource {
     pub fn subscribe_entities(&mut self, tokio: &TokioRuntime, id: String, clause: Option<Clause>) {
         if let Some(client) = self.torii.client.clone() {
             let sender = self.torii.subscription_sender.clone();
-            let task = tokio.runtime.spawn(async move {
-                let mut subscription = client
-                    .lock()
-                    .await
-                    .subscribe_entities(clause)
-                    .await
-                    .expect("Failed to subscribe");
-
-                while let Some(Ok((n, e))) = subscription.next().await {
-                    debug!("Torii subscribe entities update: {} {:?}", n, e);
-                    if let Some(ref sender) = sender {
-                        let _ = sender.lock().await.send((e.hashed_keys, e.models)).await;
-                    }
-                }
-            });
+            let task = spawn_subscription(tokio, client, sender, id.clone(), clause.clone());
 
-            // If the id already exists, we should replace the existing one.
-            tokio.runtime.block_on(async {
-                let mut subscriptions = self.torii.subscriptions.lock().await;
-                subscriptions.insert(id, task);
-            });
+            // If the id already exists, we replace the existing one.
+            let previous = self.torii.subscriptions.insert(
+                id,
+                ToriiSubscription {
+                    clause,
+                    task: Some(task),
+                    subscription_id: None,
+                },
+            );
+
+            if let Some(task) = previous.and_then(|p| p.task) {
+                task.abort();
+            }
         } else {
             warn!("No Torii client initialized, skipping subscription.");
         }
     }
+
+    /// Returns the id attributed by Torii to the subscription registered
+    /// with `subscribe_entities`, once Torii acknowledged it.
+    pub fn torii_subscription_id(&self, id: &str) -> Option<u64> {
+        self.torii
+            .subscriptions
+            .get(id)
+            .and_then(|s| s.subscription_id)
+    }
+}
+
+/// Spawns the task forwarding the updates of an entities subscription to the channel.
+///
+/// The task returns the error of the stream, if any.
+fn spawn_subscription(
+    tokio: &TokioRuntime,
+    client: Arc<Mutex<WorldClient>>,
+    sender: Option<Arc<Mutex<Sender<SubscriptionUpdate>>>>,
+    id: String,
+    clause: Option<Clause>,
+) -> JoinHandle<Result<(), torii_grpc_client::Error>> {
+    tokio.runtime.spawn(async move {
+        let mut subscription = client.lock().await.subscribe_entities(clause).await?;
+
+        let mut subscribed = false;
+
+        while let Some(message) = subscription.next().await {
+            let (subscription_id, e) = message.map_err(torii_grpc_client::Error::Grpc)?;
+            debug!(
+                "Torii subscribe entities update: {} {:?}",
+                subscription_id, e
+            );
+
+            let update =
+                subscription_update(&id, subscribed, subscription_id, e.hashed_keys, e.models);
+            subscribed = true;
+
+            if let Some(ref sender) = sender {
+                let _ = sender.lock().await.send(update).await;
+            }
+        }
+
+        warn!("Torii entities subscription ended.");
+        Ok(())
+    })
+}
+
+/// Classifies a message of the entities subscription `id`.
+///
+/// The first message of the stream only carries the id of the subscription, without
+/// any entity: it is sent as `SubscriptionUpdate::Subscribed` instead of an entity update.
+fn subscription_update(
+    id: &str,
+    subscribed: bool,
+    subscription_id: u64,
+    entity_id: Felt,
+    models: Vec<Struct>,
+) -> SubscriptionUpdate {
+    if !subscribed && entity_id == Felt::ZERO && models.is_empty() {
+        SubscriptionUpdate::Subscribed {
+            id: id.to_string(),
+            subscription_id,
+        }
+    } else {
+        SubscriptionUpdate::Entity { entity_id, models }
+    }
 }
 
 /// This task is responsible for checking if the Torii client needs to be initialized.
+///
+/// None of the tasks are awaited here: only the ones that already finished are
+/// collected, to never stall the Bevy frame while Torii is answering.
 fn check_torii_task(
     tokio: Res<TokioRuntime>,
     mut dojo: ResMut<DojoResource>,
-    mut ev_retrieve_entities: EventWriter<DojoEntityUpdated>,
+    mut ev_entities: EntityEventWriters,
     mut ev_initialized: EventWriter<DojoInitializedEvent>,
+    mut ev_connection_failed: EventWriter<DojoToriiConnectionFailed>,
+    mut ev_reconnected: EventWriter<DojoReconnected>,
+    policy: Option<Res<ReconnectPolicy>>,
 ) {
-    if let Some(task) = &mut dojo.torii.init_task {
-        if let Ok(Ok(client)) = tokio.runtime.block_on(async { task.await }) {
-            info!("Torii client initialized.");
-            dojo.torii.client = Some(Arc::new(Mutex::new(client)));
-            dojo.torii.init_task = None;
-            ev_initialized.write(DojoInitializedEvent);
+    if let Some(reconnection) = dojo.torii.reconnection {
+        if dojo.torii.init_task.is_none() && reconnection.is_due() {
+            info!(
+                "Reconnecting to Torii (attempt {}).",
+                reconnection.attempt + 1
+            );
+            dojo.torii.spawn_client(&tokio);
+        }
+    }
+
+    if let Some(result) = take_finished(&tokio, &mut dojo.torii.init_task) {
+        match result
+            .map_err(DojoError::from)
+            .and_then(|r| r.map_err(DojoError::from))
+        {
+            Ok(client) => {
+                dojo.torii.client = Some(Arc::new(Mutex::new(client)));
+                dojo.torii.status = ConnectionStatus::Connected;
+                dojo.torii.last_error = None;
+
+                let reconnection = dojo.torii.reconnection.take();
+
+                if dojo.torii.connected_once {
+                    info!("Reconnected to Torii.");
+                    dojo.torii.recovered =
+                        Some((reconnection.map_or(0, |r| r.attempt), Instant::now()));
+                    dojo.torii.resubscribe(&tokio);
+                    ev_reconnected.write(DojoReconnected {
+                        connection: DojoConnectionKind::Torii,
+                        attempts: reconnection.map_or(1, |r| r.attempt + 1),
+                    });
+                } else {
+                    info!("Torii client initialized.");
+                    dojo.torii.connected_once = true;
+                    ev_initialized.write(DojoInitializedEvent);
+                }
+            }
+            Err(error) => {
+                error!("Failed to initialize Torii client: {}", error);
+                dojo.torii.last_error = Some(error.to_string());
+                ev_connection_failed.write(DojoToriiConnectionFailed { error });
+
+                dojo.torii.reconnection =
+                    Reconnection::retry(policy.as_deref(), dojo.torii.reconnection);
+
+                dojo.torii.status = if dojo.torii.reconnection.is_some() {
+                    ConnectionStatus::Connecting
+                } else {
+                    ConnectionStatus::Failed
+                };
+            }
+        }
+    }
+
+    // A subscription stream ending or failing means the connection to Torii has been
+    // lost, the client is then recreated if a reconnection policy is set, or dropped.
+    // Subscriptions rejected by Torii are dropped.
+    let mut connection_lost = false;
+    if dojo.torii.status == ConnectionStatus::Connected {
+        let mut failed = vec![];
+
+        for (id, subscription) in dojo.torii.subscriptions.iter_mut() {
+            let Some(result) = take_finished(&tokio, &mut subscription.task) else {
+                continue;
+            };
+
+            match result {
+                Ok(Ok(())) => connection_lost = true,
+                // The stream of a subscription acknowledged by Torii failed.
+                Ok(Err(e)) if subscription.subscription_id.is_some() => {
+                    error!("Torii subscription `{}` failed: {:?}", id, e);
+                    connection_lost = true;
+                }
+                Ok(Err(e)) => {
+                    error!("Torii subscription `{}` failed: {:?}", id, e);
+                    failed.push(id.clone());
+                }
+                Err(e) => {
+                    error!("Runtime error in Torii subscription `{}`: {:?}", id, e);
+                    failed.push(id.clone());
+                }
+            }
+        }
+
+        for id in failed {
+            dojo.torii.subscriptions.remove(&id);
         }
     }
 
-    if !dojo.torii.pending_retrieve_entities.is_empty() {
-        if let Some(task) = dojo.torii.pending_retrieve_entities.pop_front() {
-            if let Ok(Ok(response)) = tokio.runtime.block_on(async { task.await }) {
+    if connection_lost {
+        dojo.torii.client = None;
+        let recovered = dojo.torii.recovered.take();
+        dojo.torii.reconnection = policy
+            .as_deref()
+            .and_then(|policy| Reconnection::after_loss(policy, recovered));
+
+        if dojo.torii.reconnection.is_some() {
+            warn!("Torii connection lost, reconnecting.");
+            dojo.torii.status = ConnectionStatus::Connecting;
+        } else {
+            let error = DojoError::ToriiConnectionLost;
+            error!("{}", error);
+            dojo.torii.last_error = Some(error.to_string());
+            dojo.torii.status = ConnectionStatus::Failed;
+            ev_connection_failed.write(DojoToriiConnectionFailed { error });
+        }
+    }
+
+    let mut budget = FrameBudget::new(dojo.budget);
+
+    for result in drain_finished(
+        &tokio,
+        &mut dojo.torii.pending_retrieve_entities,
+        &mut budget,
+    ) {
+        match result {
+            Ok(Ok(response)) => {
                 debug!("Retrieve entities response: {:?}", response);
                 for e in response.entities {
-                    ev_retrieve_entities.write(DojoEntityUpdated {
-                        entity_id: Felt::from_bytes_be_slice(&e.hashed_keys),
-                        models: e
-                            .models
-                            .into_iter()
-                            .map(|m| m.try_into().unwrap())
-                            .collect(),
-                    });
+                    let entity_id = Felt::from_bytes_be_slice(&e.hashed_keys);
+                    let models = e
+                        .models
+                        .into_iter()
+                        .map(TryInto::try_into)
+                        .collect::<Result<Vec<Struct>, _>>();
+
+                    match models {
+                        Ok(models) => ev_entities.write(entity_id, models),
+                        Err(e) => warn!("Skipping entity {:#x}, invalid model: {:?}", entity_id, e),
+                    }
                 }
             }
+            Ok(Err(e)) => error!("Retrieve entities failed: {:?}", e),
+            Err(e) => error!("Runtime error retrieving entities: {:?}", e),
         }
     }
 
-    // Pushing the subscription update to the event writer for other systems to use.
-    if let Some(receiver) = &mut dojo.torii.subscription_receiver {
-        if let Ok((entity_id, models)) = tokio.runtime.block_on(async {
-            let mut receiver = receiver.lock().await;
-            receiver.try_recv()
-        }) {
-            debug!("Torii subscription update: {:?}", (entity_id, &models));
-            ev_retrieve_entities.write(DojoEntityUpdated { entity_id, models });
+    // Pushing the subscription updates to the event writer for other systems to use.
+    // The receiver is only locked if available, a subscription task holding it
+    // will be picked up on the next frame.
+    let mut subscription_backlog = 0;
+    if let Some(receiver) = dojo.torii.subscription_receiver.clone() {
+        if let Ok(mut receiver) = receiver.try_lock() {
+            while !budget.is_exhausted() {
+                let Ok(update) = receiver.try_recv() else {
+                    break;
+                };
+
+                budget.consume();
+                debug!("Torii subscription update: {:?}", update);
+
+                match update {
+                    SubscriptionUpdate::Subscribed {
+                        id,
+                        subscription_id,
+                    } => {
+                        if let Some(subscription) = dojo.torii.subscriptions.get_mut(&id) {
+                            subscription.subscription_id = Some(subscription_id);
+                        }
+                    }
+                    SubscriptionUpdate::Entity { entity_id, models } => {
+                        ev_entities.write(entity_id, models);
+                    }
+                }
+            }
+
+            subscription_backlog = receiver.len();
         }
     }
+
+    ev_entities.flush();
+
+    dojo.backlog.retrieve_entities = dojo.torii.pending_retrieve_entities.len();
+    dojo.backlog.subscription_updates = subscription_backlog;
 }
 
 /// This task is responsible for checking the Starknet connection and transactions
 /// that have been queued to be sent to the blockchain.
-fn check_sn_task(tokio: Res<TokioRuntime>, mut dojo: ResMut<DojoResource>) {
-    if let Some(task) = &mut dojo.sn.connecting_task {
-        if let Ok(account) = tokio.runtime.block_on(async { task.await }) {
-            info!("Connected to Starknet.");
-            dojo.sn.account = Some(account);
-            dojo.sn.connecting_task = None;
+fn check_sn_task(
+    tokio: Res<TokioRuntime>,
+    mut dojo: ResMut<DojoResource>,
+    mut ev_tx: TxEventWriters,
+    mut ev_call: CallEventWriters,
+    mut ev_connection: ConnectionEventWriters,
+    policy: Option<Res<ReconnectPolicy>>,
+) {
+    if let Some(reconnection) = dojo.sn.reconnection {
+        if dojo.sn.connecting_task.is_none() && reconnection.is_due() {
+            info!(
+                "Reconnecting to Starknet (attempt {}).",
+                reconnection.attempt + 1
+            );
+            dojo.sn.spawn_connect(&tokio);
         }
     }
 
-    if !dojo.sn.pending_txs.is_empty() && dojo.sn.account.is_some() {
-        if let Some(task) = dojo.sn.pending_txs.pop_front() {
-            match tokio.runtime.block_on(async { task.await }) {
-                Ok(tx_result) => match tx_result {
-                    Ok(result) => {
-                        info!("Transaction completed: {:#x}", result.transaction_hash);
-                    }
-                    Err(e) => error!("Transaction failed with account error: {:?}", e),
-                },
-                Err(e) => error!("Runtime error executing transaction: {:?}", e),
+    if let Some(result) = take_finished(&tokio, &mut dojo.sn.connecting_task) {
+        match result.map_err(DojoError::from).and_then(|r| r) {
+            Ok(account) => {
+                info!("Connected to Starknet.");
+                dojo.sn.account = Some(account);
+                // The nonce of the new account is fetched by its first transaction.
+                dojo.sn.nonces = NonceTracker::default();
+                dojo.sn.status = ConnectionStatus::Connected;
+                dojo.sn.last_error = None;
+
+                if let Some(reconnection) = dojo.sn.reconnection.take() {
+                    ev_connection.reconnected.write(DojoReconnected {
+                        connection: DojoConnectionKind::Starknet,
+                        attempts: reconnection.attempt + 1,
+                    });
+                }
+            }
+            Err(error) => {
+                error!("Failed to connect to Starknet: {}", error);
+                dojo.sn.last_error = Some(error.to_string());
+                ev_connection.failed.write(DojoConnectionFailed { error });
+
+                dojo.sn.reconnection = Reconnection::retry(policy.as_deref(), dojo.sn.reconnection);
+
+                dojo.sn.status = if dojo.sn.reconnection.is_some() {
+                    ConnectionStatus::Connecting
+                } else {
+                    ConnectionStatus::Failed
+                };
+            }
+        }
+    }
+
+    if dojo.sn.tx_batch.is_due(dojo.sn.tx_config.batching) {
+        dojo.flush_tx_batch(&tokio);
+    }
+
+    let mut budget = FrameBudget::new(dojo.budget);
+
+    // The transactions tasks report their outcome through the updates channel,
+    // only runtime errors are left to be handled when joining them.
+    for result in drain_finished(&tokio, &mut dojo.sn.pending_txs, &mut budget) {
+        if let Err(e) = result {
+            error!("Runtime error executing transaction: {:?}", e);
+        }
+    }
+
+    while !budget.is_exhausted() {
+        let Ok((id, update)) = dojo.sn.tx_update_receiver.try_recv() else {
+            break;
+        };
+
+        budget.consume();
+
+        match update {
+            TxUpdate::FeeEstimated { estimate } => {
+                debug!("Transaction fee estimated: {:?}", estimate);
+                ev_tx
+                    .fee_estimated
+                    .write(DojoTxFeeEstimated { id, estimate });
+            }
+            TxUpdate::Submitted { transaction_hash } => {
+                debug!("Transaction submitted: {:#x}", transaction_hash);
+                ev_tx.submitted.write(DojoTxSubmitted {
+                    id,
+                    transaction_hash,
+                });
+            }
+            TxUpdate::Accepted {
+                transaction_hash,
+                receipt,
+            } => {
+                info!(
+                    "Transaction completed: {:#x} ({:?})",
+                    transaction_hash, receipt.finality_status
+                );
+                ev_tx.accepted.write(DojoTxAccepted {
+                    id,
+                    transaction_hash,
+                    receipt,
+                });
+            }
+            TxUpdate::Reverted {
+                transaction_hash,
+                reason,
+                receipt,
+            } => {
+                warn!("Transaction reverted: {}", reason);
+                ev_tx.reverted.write(DojoTxReverted {
+                    id,
+                    transaction_hash,
+                    reason,
+                    receipt,
+                });
+            }
+            TxUpdate::Failed {
+                transaction_hash,
+                error,
+            } => {
+                error!("Transaction failed: {}", error);
+                ev_tx.failed.write(DojoTxFailed {
+                    id,
+                    transaction_hash,
+                    error,
+                });
+            }
+        }
+    }
+
+    for result in drain_finished(&tokio, &mut dojo.sn.pending_calls, &mut budget) {
+        match result {
+            Ok((id, Ok(result))) => {
+                debug!("Call {:?} returned: {:?}", id, result);
+                ev_call.result.write(DojoCallResult { id, result });
+            }
+            Ok((id, Err(error))) => {
+                error!("Call failed: {}", error);
+                ev_call.failed.write(DojoCallFailed { id, error });
+            }
+            Err(e) => error!("Runtime error executing call: {:?}", e),
+        }
+    }
+
+    for result in drain_finished(&tokio, &mut dojo.sn.pending_fee_estimates, &mut budget) {
+        match result {
+            Ok((id, Ok(estimate))) => {
+                debug!("Fee estimate {:?}: {:?}", id, estimate);
+                ev_call
+                    .fee_estimated
+                    .write(DojoFeeEstimated { id, estimate });
+            }
+            Ok((id, Err(error))) => {
+                error!("Fee estimation failed: {}", error);
+                ev_call
+                    .fee_estimate_failed
+                    .write(DojoFeeEstimateFailed { id, error });
+            }
+            Err(e) => error!("Runtime error estimating fee: {:?}", e),
+        }
+    }
+
+    dojo.backlog.transactions = dojo.sn.pending_txs.len() + dojo.sn.tx_batch.len();
+    dojo.backlog.calls = dojo.sn.pending_calls.len() + dojo.sn.pending_fee_estimates.len();
+}
+
+/// An entity event buffered until the end of `check_torii_task`.
+enum EntityEvent {
+    Updated(DojoEntityUpdated),
+    Deleted(DojoEntityDeleted),
+}
+
+/// Writers for the entities events.
+///
+/// The events are buffered during the frame, and written in their arrival order
+/// by `flush`. An update followed by a deletion of the same entity or model in the
+/// same frame is dropped, since the deletions are applied before the updates.
+#[derive(SystemParam)]
+struct EntityEventWriters<'w, 's> {
+    updated: EventWriter<'w, DojoEntityUpdated>,
+    deleted: EventWriter<'w, DojoEntityDeleted>,
+    buffer: Local<'s, Vec<EntityEvent>>,
+}
+
+impl EntityEventWriters<'_, '_> {
+    /// Buffers the updated models and the deleted ones (without any member)
+    /// as separate events.
+    fn write(&mut self, entity_id: Felt, models: Vec<Struct>) {
+        if models.is_empty() {
+            self.discard_updates(entity_id, None);
+            self.buffer.push(EntityEvent::Deleted(DojoEntityDeleted {
+                entity_id,
+                models_removed: vec![],
+            }));
+            return;
+        }
+
+        let (removed, models): (Vec<_>, Vec<_>) =
+            models.into_iter().partition(|m| m.children.is_empty());
+
+        if !models.is_empty() {
+            self.buffer.push(EntityEvent::Updated(DojoEntityUpdated {
+                entity_id,
+                models,
+            }));
+        }
+
+        if !removed.is_empty() {
+            let models_removed: Vec<_> = removed.into_iter().map(|m| m.name).collect();
+            self.discard_updates(entity_id, Some(&models_removed));
+            self.buffer.push(EntityEvent::Deleted(DojoEntityDeleted {
+                entity_id,
+                models_removed,
+            }));
+        }
+    }
+
+    /// Drops the buffered updates of the deleted models of the entity,
+    /// or of all its models if `models_removed` is `None`.
+    fn discard_updates(&mut self, entity_id: Felt, models_removed: Option<&[String]>) {
+        self.buffer.retain_mut(|ev| {
+            let EntityEvent::Updated(update) = ev else {
+                return true;
+            };
+
+            if update.entity_id != entity_id {
+                return true;
+            }
+
+            match models_removed {
+                Some(removed) => {
+                    update.models.retain(|m| !removed.contains(&m.name));
+                    !update.models.is_empty()
+                }
+                None => false,
+            }
+        });
+    }
+
+    /// Writes the buffered events.
+    fn flush(&mut self) {
+        for ev in self.buffer.drain(..) {
+            match ev {
+                EntityEvent::Updated(ev) => {
+                    self.updated.write(ev);
+                }
+                EntityEvent::Deleted(ev) => {
+                    self.deleted.write(ev);
+                }
+            }
+        }
+    }
+}
+
+/// Writers for the transactions lifecycle events.
+#[derive(SystemParam)]
+struct TxEventWriters<'w> {
+    fee_estimated: EventWriter<'w, DojoTxFeeEstimated>,
+    submitted: EventWriter<'w, DojoTxSubmitted>,
+    accepted: EventWriter<'w, DojoTxAccepted>,
+    reverted: EventWriter<'w, DojoTxReverted>,
+    failed: EventWriter<'w, DojoTxFailed>,
+}
+
+/// Writers for the read-only calls and fee estimates events.
+#[derive(SystemParam)]
+struct CallEventWriters<'w> {
+    result: EventWriter<'w, DojoCallResult>,
+    failed: EventWriter<'w, DojoCallFailed>,
+    fee_estimated: EventWriter<'w, DojoFeeEstimated>,
+    fee_estimate_failed: EventWriter<'w, DojoFeeEstimateFailed>,
+}
+
+/// Writers for the Starknet connection events.
+#[derive(SystemParam)]
+struct ConnectionEventWriters<'w> {
+    failed: EventWriter<'w, DojoConnectionFailed>,
+    reconnected: EventWriter<'w, DojoReconnected>,
+}
+
+/// Tracks the [`PollBudget`] consumed by a system during the current frame.
+pub(crate) struct FrameBudget {
+    limits: PollBudget,
+    started_at: Instant,
+    used: usize,
+}
+
+impl FrameBudget {
+    pub(crate) fn new(limits: PollBudget) -> Self {
+        Self {
+            limits,
+            started_at: Instant::now(),
+            used: 0,
+        }
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.limits.max_updates.is_some_and(|max| self.used >= max)
+            || self
+                .limits
+                .max_duration
+                .is_some_and(|max| self.started_at.elapsed() >= max)
+    }
+
+    fn consume(&mut self) {
+        self.used += 1;
+    }
+}
+
+/// Takes the output of the task if it has finished, leaving `None` in its place.
+///
+/// The task is only joined once `is_finished` returns true, hence joining
+/// it never blocks.
+fn take_finished<T>(
+    tokio: &TokioRuntime,
+    task: &mut Option<JoinHandle<T>>,
+) -> Option<Result<T, JoinError>> {
+    if !task.as_ref().is_some_and(|t| t.is_finished()) {
+        return None;
+    }
+
+    task.take().map(|t| tokio.runtime.block_on(t))
+}
+
+/// Removes the finished tasks from the queue and returns their outputs,
+/// in queue order, until the budget is exhausted.
+/// Tasks still running are left in the queue.
+pub(crate) fn drain_finished<T>(
+    tokio: &TokioRuntime,
+    tasks: &mut VecDeque<JoinHandle<T>>,
+    budget: &mut FrameBudget,
+) -> Vec<Result<T, JoinError>> {
+    let mut outputs = Vec::new();
+    let mut i = 0;
+
+    while i < tasks.len() && !budget.is_exhausted() {
+        if tasks[i].is_finished() {
+            if let Some(task) = tasks.remove(i) {
+                budget.consume();
+                outputs.push(tokio.runtime.block_on(task));
             }
+        } else {
+            i += 1;
         }
     }
+
+    outputs
+}
+
+/// Parses the RPC URL and builds the provider for it.
+pub(crate) fn rpc_provider(rpc_url: &str) -> Result<AnyProvider, DojoError> {
+    let url = Url::parse(rpc_url).map_err(|source| DojoError::InvalidUrl {
+        url: rpc_url.to_string(),
+        source,
+    })?;
+
+    Ok(AnyProvider::JsonRpcHttp(JsonRpcClient::new(
+        HttpTransport::new(url),
+    )))
+}
+
+/// Fetches the chain id of the provider, checking it against the expected one if any.
+async fn fetch_chain_id(
+    provider: &AnyProvider,
+    expected_chain_id: Option<Felt>,
+) -> Result<Felt, DojoError> {
+    let chain_id = provider.chain_id().await?;
+
+    match expected_chain_id {
+        Some(expected) if expected != chain_id => Err(DojoError::ChainIdMismatch {
+            expected,
+            actual: chain_id,
+        }),
+        _ => Ok(chain_id),
+    }
+}
+
+/// Connects to the account of the given source.
+async fn connect_account_source(
+    rpc_url: String,
+    source: AccountSource,
+    expected_chain_id: Option<Felt>,
+) -> Result<DojoAccount, DojoError> {
+    match source {
+        AccountSource::Session(config) => {
+            let sender =
+                connect_single_owner(rpc_url, *config.sender.clone(), expected_chain_id).await?;
+            Ok(DojoAccount::Session(SessionAccount::new(sender, config)))
+        }
+        source => connect_single_owner(rpc_url, source, expected_chain_id)
+            .await
+            .map(DojoAccount::SingleOwner),
+    }
+}
+
+/// Connects to the single owner account of the given source, which can't be a session.
+pub(crate) async fn connect_single_owner(
+    rpc_url: String,
+    source: AccountSource,
+    expected_chain_id: Option<Felt>,
+) -> Result<SingleOwnerAccount<AnyProvider, DojoWallet>, DojoError> {
+    match source {
+        AccountSource::PrivateKey {
+            address,
+            private_key,
+        } => {
+            let signer = DojoWallet::from_private_key(private_key);
+            connect_to_starknet(rpc_url, address, signer, expected_chain_id).await
+        }
+        AccountSource::Signer { address, signer } => {
+            let signer = DojoWallet(signer);
+            connect_to_starknet(rpc_url, address, signer, expected_chain_id).await
+        }
+        AccountSource::Keystore {
+            address,
+            path,
+            password,
+        } => {
+            let wallet =
+                tokio::task::spawn_blocking(move || load_keystore(path, &password)).await??;
+            let signer = DojoWallet(Arc::new(wallet));
+            connect_to_starknet(rpc_url, address, signer, expected_chain_id).await
+        }
+        AccountSource::Predeployed(idx) => {
+            connect_predeployed_account(rpc_url, idx, expected_chain_id).await
+        }
+        AccountSource::Session(_) => Err(DojoError::SessionSender),
+    }
 }
 
 /// Connects to a Starknet account by creating a single owner account.
 async fn connect_to_starknet(
     rpc_url: String,
     account_addr: Felt,
-    private_key: Felt,
-) -> Arc<SingleOwnerAccount<AnyProvider, LocalWallet>> {
-    // let rpc_url = Url::parse("http://0.0.0.0:5050").expect("Expecting Starknet RPC URL");
-    let provider = AnyProvider::JsonRpcHttp(JsonRpcClient::new(HttpTransport::new(
-        Url::parse(&rpc_url).expect("Expecting valid Starknet RPC URL"),
-    )));
+    signer: DojoWallet,
+    expected_chain_id: Option<Felt>,
+) -> Result<SingleOwnerAccount<AnyProvider, DojoWallet>, DojoError> {
+    let provider = rpc_provider(&rpc_url)?;
+    let chain_id = fetch_chain_id(&provider, expected_chain_id).await?;
 
-    let chain_id = provider.chain_id().await.unwrap();
-
-    let signer = LocalWallet::from(SigningKey::from_secret_scalar(private_key));
     let address = account_addr;
 
-    Arc::new(SingleOwnerAccount::new(
-        provider,
-        signer,
-        address,
-        chain_id,
-        ExecutionEncoding::New,
-    ))
+    let mut account =
+        SingleOwnerAccount::new(provider, signer, address, chain_id, ExecutionEncoding::New);
+
+    // The nonce is fetched from the pending block, to account for the
+    // transactions not yet included in a block.
+    account.set_block_id(BlockId::Tag(BlockTag::Pending));
+
+    Ok(account)
 }
 
 /// Connects to a predeployed account by fetching the accounts from the RPC.
@@ -322,10 +1550,9 @@ async fn connect_to_starknet(
 pub async fn connect_predeployed_account(
     rpc_url: String,
     account_idx: usize,
-) -> Arc<SingleOwnerAccount<AnyProvider, LocalWallet>> {
-    let provider = AnyProvider::JsonRpcHttp(JsonRpcClient::new(HttpTransport::new(
-        Url::parse(&rpc_url).unwrap(),
-    )));
+    expected_chain_id: Option<Felt>,
+) -> Result<SingleOwnerAccount<AnyProvider, DojoWallet>, DojoError> {
+    let provider = rpc_provider(&rpc_url)?;
 
     let client = reqwest::Client::new();
     let response = client
@@ -337,52 +1564,196 @@ pub async fn connect_predeployed_account(
             "id": 1
         }))
         .send()
-        .await
-        .expect("Failed to fetch predeployed accounts.");
+        .await?;
 
-    let result: serde_json::Value = response
-        .json()
-        .await
-        .expect("Failed to parse predeployed accounts.");
+    let result: serde_json::Value = response.json().await?;
 
-    if let Some(vals) = result.get("result").and_then(|v| v.as_array()) {
-        let chain_id = provider.chain_id().await.expect("Failed to get chain id.");
+    let accounts = result
+        .get("result")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| DojoError::InvalidResponse(result.to_string()))?;
 
-        for (i, a) in vals.iter().enumerate() {
-            let address = a["address"].as_str().unwrap();
+    let account = accounts
+        .get(account_idx)
+        .ok_or(DojoError::AccountIndexMissing(account_idx))?;
 
-            // On slot, some accounts are hidden, we skip them.
-            let private_key = if let Some(pk) = a["privateKey"].as_str() {
-                pk
-            } else {
-                continue;
-            };
+    // On slot, some accounts are hidden and can't be used.
+    let private_key = account["privateKey"]
+        .as_str()
+        .ok_or(DojoError::HiddenAccount(account_idx))?;
 
-            let provider = AnyProvider::JsonRpcHttp(JsonRpcClient::new(HttpTransport::new(
-                Url::parse(&rpc_url).unwrap(),
-            )));
+    let private_key = Felt::from_hex(private_key)
+        .map_err(|e| DojoError::InvalidResponse(format!("invalid private key: {}", e)))?;
 
-            let signer = LocalWallet::from(SigningKey::from_secret_scalar(
-                Felt::from_hex(private_key).unwrap(),
-            ));
+    let address = account["address"]
+        .as_str()
+        .and_then(|a| Felt::from_hex(a).ok())
+        .ok_or_else(|| DojoError::InvalidResponse(format!("invalid address: {}", account)))?;
 
-            let mut account = SingleOwnerAccount::new(
-                provider,
-                signer,
-                Felt::from_hex(address).unwrap(),
-                chain_id,
-                ExecutionEncoding::New,
-            );
+    let chain_id = fetch_chain_id(&provider, expected_chain_id).await?;
 
-            account.set_block_id(BlockId::Tag(BlockTag::Pending));
+    let signer = DojoWallet::from_private_key(private_key);
 
-            let account = Arc::new(account);
+    let mut account =
+        SingleOwnerAccount::new(provider, signer, address, chain_id, ExecutionEncoding::New);
 
-            if i == account_idx {
-                return account;
-            }
+    account.set_block_id(BlockId::Tag(BlockTag::Pending));
+
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use dojo_types::schema::Member;
+
+    /// Builds a model with a single member, or a deleted model without any member.
+    fn model(tag: &str, x: Option<u32>) -> Struct {
+        Struct {
+            name: tag.to_string(),
+            children: x
+                .map(|x| Member {
+                    name: "x".to_string(),
+                    ty: x.to_ty(),
+                    key: false,
+                })
+                .into_iter()
+                .collect(),
         }
     }
 
-    panic!("Account index out of bounds.");
+    #[test]
+    fn entity_events_discard_updates_before_deletion() {
+        let mut world = World::new();
+        world.init_resource::<Events<DojoEntityUpdated>>();
+        world.init_resource::<Events<DojoEntityDeleted>>();
+
+        world
+            .run_system_once(|mut ev_entities: EntityEventWriters| {
+                ev_entities.write(
+                    Felt::ONE,
+                    vec![model("di-Position", Some(1)), model("di-Moves", Some(2))],
+                );
+                ev_entities.write(Felt::ONE, vec![model("di-Position", None)]);
+                ev_entities.write(Felt::TWO, vec![model("di-Position", Some(3))]);
+                ev_entities.write(Felt::TWO, vec![]);
+                ev_entities.write(Felt::TWO, vec![model("di-Position", Some(4))]);
+                ev_entities.flush();
+            })
+            .unwrap();
+
+        let updated: Vec<_> = world
+            .resource_mut::<Events<DojoEntityUpdated>>()
+            .drain()
+            .map(|ev| (ev.entity_id, ev.models))
+            .collect();
+        assert_eq!(
+            updated,
+            [
+                (Felt::ONE, vec![model("di-Moves", Some(2))]),
+                (Felt::TWO, vec![model("di-Position", Some(4))]),
+            ]
+        );
+
+        let deleted: Vec<_> = world
+            .resource_mut::<Events<DojoEntityDeleted>>()
+            .drain()
+            .map(|ev| (ev.entity_id, ev.models_removed))
+            .collect();
+        assert_eq!(
+            deleted,
+            [
+                (Felt::ONE, vec!["di-Position".to_string()]),
+                (Felt::TWO, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn subscription_acknowledgment() {
+        let update = subscription_update("moves", false, 3, Felt::ZERO, vec![]);
+        assert!(matches!(
+            update,
+            SubscriptionUpdate::Subscribed { id, subscription_id: 3 } if id == "moves"
+        ));
+    }
+
+    #[test]
+    fn subscription_entity_updates() {
+        // An entity sent as the first message.
+        let update = subscription_update(
+            "moves",
+            false,
+            3,
+            Felt::ONE,
+            vec![model("di-Position", Some(1))],
+        );
+        assert!(matches!(
+            update,
+            SubscriptionUpdate::Entity { entity_id, models }
+                if entity_id == Felt::ONE && models == [model("di-Position", Some(1))]
+        ));
+
+        // Models of the zero entity sent as the first message.
+        let update = subscription_update(
+            "moves",
+            false,
+            3,
+            Felt::ZERO,
+            vec![model("di-Position", Some(1))],
+        );
+        assert!(matches!(update, SubscriptionUpdate::Entity { .. }));
+
+        // An empty message once acknowledged is an entity update.
+        let update = subscription_update("moves", true, 3, Felt::ZERO, vec![]);
+        assert!(matches!(
+            update,
+            SubscriptionUpdate::Entity { entity_id, models }
+                if entity_id == Felt::ZERO && models.is_empty()
+        ));
+    }
+
+    #[test]
+    fn torii_connection_lost_without_policy() {
+        let mut world = World::new();
+        world.init_resource::<TokioRuntime>();
+        world.init_resource::<DojoResource>();
+        world.init_resource::<Events<DojoEntityUpdated>>();
+        world.init_resource::<Events<DojoEntityDeleted>>();
+        world.init_resource::<Events<DojoInitializedEvent>>();
+        world.init_resource::<Events<DojoToriiConnectionFailed>>();
+        world.init_resource::<Events<DojoReconnected>>();
+
+        // A subscription stream ending, as when the connection is lost.
+        let task = world
+            .resource::<TokioRuntime>()
+            .runtime
+            .spawn(async { Ok::<_, torii_grpc_client::Error>(()) });
+        while !task.is_finished() {
+            std::thread::yield_now();
+        }
+
+        let mut dojo = world.resource_mut::<DojoResource>();
+        dojo.torii.status = ConnectionStatus::Connected;
+        dojo.torii.subscriptions.insert(
+            "moves".to_string(),
+            ToriiSubscription {
+                clause: None,
+                task: Some(task),
+                subscription_id: None,
+            },
+        );
+
+        world.run_system_once(check_torii_task).unwrap();
+
+        let dojo = world.resource::<DojoResource>();
+        assert_eq!(dojo.torii.status, ConnectionStatus::Failed);
+        assert!(dojo.torii.client.is_none());
+        assert!(dojo.torii.reconnection.is_none());
+        assert_eq!(
+            world.resource::<Events<DojoToriiConnectionFailed>>().len(),
+            1
+        );
+    }
 }
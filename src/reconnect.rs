@@ -0,0 +1,171 @@
+//! Automatic reconnection to Torii and Starknet.
+//!
+//! Reconnection is opt-in, by providing a [`ReconnectPolicy`] to the Dojo plugin.
+//! Failed connection attempts are then retried with an exponential backoff,
+//! and a lost Torii connection is recreated along with its subscriptions.
+//!
+//! A lost Torii connection is detected by the end of a subscription stream:
+//! with no active subscription, a restart of Torii is never detected.
+
+use bevy::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Policy used to retry the connections to Torii and Starknet.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ReconnectPolicy {
+    /// Delay before the first reconnection attempt.
+    pub initial_delay: Duration,
+    /// Upper bound of the delay between two attempts.
+    pub max_delay: Duration,
+    /// Number of attempts before giving up, unlimited if `None`.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            max_attempts: Some(10),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns the delay to wait before the given attempt (starting at 0).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay)
+    }
+}
+
+/// The connection that has been recovered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DojoConnectionKind {
+    Torii,
+    Starknet,
+}
+
+/// This event is emitted when a connection is recovered after one or more failed attempts.
+#[derive(Event, Debug)]
+pub struct DojoReconnected {
+    pub connection: DojoConnectionKind,
+    pub attempts: u32,
+}
+
+/// A reconnection attempt waiting for its backoff delay.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Reconnection {
+    pub attempt: u32,
+    pub next_attempt_at: Instant,
+}
+
+impl Reconnection {
+    /// Schedules the given attempt, or returns `None` if the policy gives up.
+    pub fn schedule(policy: &ReconnectPolicy, attempt: u32) -> Option<Self> {
+        if policy.max_attempts.is_some_and(|max| attempt >= max) {
+            return None;
+        }
+
+        Some(Self {
+            attempt,
+            next_attempt_at: Instant::now() + policy.delay(attempt),
+        })
+    }
+
+    /// Schedules the attempt following a failed connection.
+    ///
+    /// `failed` is the reconnection attempt that just failed, if any.
+    /// Returns `None` if there is no policy or if the policy gives up.
+    pub fn retry(policy: Option<&ReconnectPolicy>, failed: Option<Self>) -> Option<Self> {
+        let attempt = failed.map_or(0, |f| f.attempt + 1);
+        policy.and_then(|policy| Self::schedule(policy, attempt))
+    }
+
+    /// Schedules the reconnection after a lost connection.
+    ///
+    /// `recovered` is the attempt of the last successful reconnection, and the time it
+    /// succeeded. A connection lost again within `max_delay` continues these attempts,
+    /// so that `max_attempts` applies to a connection lost right after every reconnection.
+    pub fn after_loss(policy: &ReconnectPolicy, recovered: Option<(u32, Instant)>) -> Option<Self> {
+        let attempt = match recovered {
+            Some((attempt, at)) if at.elapsed() < policy.max_delay => attempt + 1,
+            _ => 0,
+        };
+        Self::schedule(policy, attempt)
+    }
+
+    pub fn is_due(&self) -> bool {
+        Instant::now() >= self.next_attempt_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            max_attempts: Some(3),
+        }
+    }
+
+    #[test]
+    fn delay_doubles_up_to_max_delay() {
+        let policy = policy();
+
+        assert_eq!(policy.delay(0), Duration::from_secs(1));
+        assert_eq!(policy.delay(1), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(8));
+        assert_eq!(policy.delay(4), Duration::from_secs(10));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn retry_increments_attempts_until_max_attempts() {
+        let policy = policy();
+
+        assert!(Reconnection::retry(None, None).is_none());
+
+        let first = Reconnection::retry(Some(&policy), None).unwrap();
+        assert_eq!(first.attempt, 0);
+
+        let second = Reconnection::retry(Some(&policy), Some(first)).unwrap();
+        assert_eq!(second.attempt, 1);
+
+        let third = Reconnection::retry(Some(&policy), Some(second)).unwrap();
+        assert_eq!(third.attempt, 2);
+
+        assert!(Reconnection::retry(Some(&policy), Some(third)).is_none());
+    }
+
+    #[test]
+    fn unlimited_attempts() {
+        let policy = ReconnectPolicy {
+            max_attempts: None,
+            ..policy()
+        };
+
+        assert!(Reconnection::schedule(&policy, 1_000).is_some());
+    }
+
+    #[test]
+    fn loss_after_recent_reconnection_continues_attempts() {
+        let policy = policy();
+
+        let after_loss = Reconnection::after_loss(&policy, None).unwrap();
+        assert_eq!(after_loss.attempt, 0);
+
+        let recent = Reconnection::after_loss(&policy, Some((1, Instant::now()))).unwrap();
+        assert_eq!(recent.attempt, 2);
+
+        assert!(Reconnection::after_loss(&policy, Some((2, Instant::now()))).is_none());
+
+        let stable = Instant::now() - Duration::from_secs(60);
+        let old = Reconnection::after_loss(&policy, Some((2, stable))).unwrap();
+        assert_eq!(old.attempt, 0);
+    }
+}
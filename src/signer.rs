@@ -0,0 +1,223 @@
+//! Signers of the Starknet accounts.
+//!
+//! Accounts are signed by an `Arc<dyn DojoSigner>`, which can hold a private key
+//! (a `LocalWallet`, also loaded from an encrypted keystore), forward the hashes to
+//! a remote signer over HTTP, or be implemented by the game (a test signer for instance).
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use starknet::core::crypto::Signature;
+use starknet::core::types::Felt;
+use starknet::signers::{
+    LocalWallet, Signer, SignerInteractivityContext, SigningKey, VerifyingKey,
+};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error::DojoSignerError;
+
+/// A signer of transactions, usable as a trait object.
+#[async_trait]
+pub trait DojoSigner: Send + Sync {
+    /// Returns the public key of the signer.
+    async fn get_public_key(&self) -> Result<VerifyingKey, DojoSignerError>;
+
+    /// Signs the hash.
+    async fn sign_hash(&self, hash: &Felt) -> Result<Signature, DojoSignerError>;
+
+    /// Returns true if signing requires an interaction (a hardware wallet for instance),
+    /// in which case the fees are estimated without signing the transaction.
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+#[async_trait]
+impl DojoSigner for LocalWallet {
+    async fn get_public_key(&self) -> Result<VerifyingKey, DojoSignerError> {
+        Signer::get_public_key(self)
+            .await
+            .map_err(|e| DojoSignerError::Signing(e.to_string()))
+    }
+
+    async fn sign_hash(&self, hash: &Felt) -> Result<Signature, DojoSignerError> {
+        Signer::sign_hash(self, hash)
+            .await
+            .map_err(|e| DojoSignerError::Signing(e.to_string()))
+    }
+}
+
+/// Loads the private key of an encrypted JSON keystore, as created by `starkli`.
+///
+/// Decrypting a keystore is intentionally slow, this function must not be
+/// called from a Bevy system.
+pub fn load_keystore(
+    path: impl AsRef<Path>,
+    password: &str,
+) -> Result<LocalWallet, DojoSignerError> {
+    let key = SigningKey::from_keystore(path, password)
+        .map_err(|e| DojoSignerError::Keystore(e.to_string()))?;
+
+    Ok(LocalWallet::from(key))
+}
+
+/// A signer forwarding the hashes to a remote signer over HTTP.
+///
+/// The remote signer returns its public key on `GET <url>/public_key`, as
+/// `{ "public_key": "0x..." }`, and signs the hashes posted on `POST <url>/sign`
+/// as `{ "hash": "0x..." }`, returning `{ "r": "0x...", "s": "0x..." }`.
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    url: String,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct PublicKeyResponse {
+    public_key: Felt,
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    r: Felt,
+    s: Felt,
+}
+
+impl RemoteSigner {
+    /// Creates a signer for the remote signer at the given URL.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl DojoSigner for RemoteSigner {
+    async fn get_public_key(&self) -> Result<VerifyingKey, DojoSignerError> {
+        let response: PublicKeyResponse = self
+            .client
+            .get(format!("{}/public_key", self.url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(VerifyingKey::from_scalar(response.public_key))
+    }
+
+    async fn sign_hash(&self, hash: &Felt) -> Result<Signature, DojoSignerError> {
+        let response: SignResponse = self
+            .client
+            .post(format!("{}/sign", self.url))
+            .json(&serde_json::json!({ "hash": hash }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(Signature {
+            r: response.r,
+            s: response.s,
+        })
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+/// A `DojoSigner` usable as the signer of a starknet-rs account.
+#[derive(Clone)]
+pub struct DojoWallet(pub Arc<dyn DojoSigner>);
+
+impl DojoWallet {
+    /// Creates the wallet of a private key.
+    pub fn from_private_key(private_key: Felt) -> Self {
+        Self(Arc::new(LocalWallet::from(SigningKey::from_secret_scalar(
+            private_key,
+        ))))
+    }
+}
+
+#[async_trait]
+impl Signer for DojoWallet {
+    type GetPublicKeyError = DojoSignerError;
+    type SignError = DojoSignerError;
+
+    async fn get_public_key(&self) -> Result<VerifyingKey, Self::GetPublicKeyError> {
+        self.0.get_public_key().await
+    }
+
+    async fn sign_hash(&self, hash: &Felt) -> Result<Signature, Self::SignError> {
+        self.0.sign_hash(hash).await
+    }
+
+    fn is_interactive(&self, _context: SignerInteractivityContext<'_>) -> bool {
+        self.0.is_interactive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A signer implemented by the game, counting the signed hashes.
+    struct TestSigner {
+        key: SigningKey,
+        signed: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl DojoSigner for TestSigner {
+        async fn get_public_key(&self) -> Result<VerifyingKey, DojoSignerError> {
+            Ok(self.key.verifying_key())
+        }
+
+        async fn sign_hash(&self, hash: &Felt) -> Result<Signature, DojoSignerError> {
+            self.signed.fetch_add(1, Ordering::Relaxed);
+            self.key
+                .sign(hash)
+                .map_err(|e| DojoSignerError::Signing(e.to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_signer() {
+        let key = SigningKey::from_secret_scalar(Felt::from(0x1234));
+        let expected_public_key = key.verifying_key().scalar();
+        let signer = Arc::new(TestSigner {
+            key,
+            signed: AtomicUsize::new(0),
+        });
+        let wallet = DojoWallet(signer.clone());
+
+        let public_key = Signer::get_public_key(&wallet).await.unwrap();
+        assert_eq!(public_key.scalar(), expected_public_key);
+
+        let hash = Felt::from(0x5678);
+        let signature = Signer::sign_hash(&wallet, &hash).await.unwrap();
+        assert!(public_key.verify(&hash, &signature).unwrap());
+        assert_eq!(signer.signed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn load_keystore_wrong_password() {
+        let path =
+            std::env::temp_dir().join(format!("dojo-bevy-keystore-{}.json", std::process::id()));
+        SigningKey::from_secret_scalar(Felt::from(0x1234))
+            .save_as_keystore(&path, "password")
+            .unwrap();
+
+        let loaded = load_keystore(&path, "password");
+        let wrong_password = load_keystore(&path, "wrong");
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(loaded.is_ok());
+        assert!(matches!(wrong_password, Err(DojoSignerError::Keystore(_))));
+    }
+}
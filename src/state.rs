@@ -0,0 +1,72 @@
+//! Connection states exposed as Bevy [`States`].
+//!
+//! The states mirror the [`ConnectionStatus`] of the Torii and Starknet connections,
+//! which allows the use of `run_if(in_state(...))` and `OnEnter(...)` schedules
+//! instead of polling the `DojoResource`.
+//!
+//! The Dojo plugin adds the `StatesPlugin` (part of the `DefaultPlugins`) if missing.
+
+use bevy::prelude::*;
+
+use crate::plugin::{ConnectionStatus, DojoResource};
+
+/// State of the Torii connection.
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ToriiState {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+    Failed,
+}
+
+/// State of the Starknet connection.
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum StarknetState {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+    Failed,
+}
+
+impl From<ConnectionStatus> for ToriiState {
+    fn from(status: ConnectionStatus) -> Self {
+        match status {
+            ConnectionStatus::Disconnected => Self::Disconnected,
+            ConnectionStatus::Connecting => Self::Connecting,
+            ConnectionStatus::Connected => Self::Connected,
+            ConnectionStatus::Failed => Self::Failed,
+        }
+    }
+}
+
+impl From<ConnectionStatus> for StarknetState {
+    fn from(status: ConnectionStatus) -> Self {
+        match status {
+            ConnectionStatus::Disconnected => Self::Disconnected,
+            ConnectionStatus::Connecting => Self::Connecting,
+            ConnectionStatus::Connected => Self::Connected,
+            ConnectionStatus::Failed => Self::Failed,
+        }
+    }
+}
+
+/// Queues a state transition when the status of a connection changed.
+pub(crate) fn sync_connection_states(
+    dojo: Res<DojoResource>,
+    torii_state: Res<State<ToriiState>>,
+    sn_state: Res<State<StarknetState>>,
+    mut next_torii_state: ResMut<NextState<ToriiState>>,
+    mut next_sn_state: ResMut<NextState<StarknetState>>,
+) {
+    let torii = ToriiState::from(dojo.torii.status);
+    if *torii_state.get() != torii {
+        next_torii_state.set(torii);
+    }
+
+    let sn = StarknetState::from(dojo.sn.status);
+    if *sn_state.get() != sn {
+        next_sn_state.set(sn);
+    }
+}
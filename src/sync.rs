@@ -0,0 +1,483 @@
+//! Synchronisation of the Dojo models with Bevy components.
+//!
+//! Models registered with [`DojoAppExt::register_dojo_model`] are inserted as
+//! components on a Bevy entity maintained for every Dojo entity, identified by
+//! the `entity_id` (hashed keys) sent by Torii. The components are updated
+//! every time a `DojoEntityUpdated` event carries the model, and removed when a
+//! `DojoEntityDeleted` event reports the model as deleted. Deleted entities are despawned.
+//!
+//! The deletions of a frame are applied before its updates. Updates followed by a
+//! deletion of the same entity or model are not emitted, so that the deletion wins.
+//!
+//! A [`DojoModelUpdated`] event is also emitted for every update of a registered
+//! model, for systems reacting to the updates rather than querying the components.
+//!
+//! The [`DojoEntityMap`] resource maps the Dojo entities to their Bevy entity,
+//! which also holds the [`DojoEntityId`] component. Games can also map the entities
+//! they spawn themselves with [`DojoEntityMap::insert`].
+
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use starknet::core::types::Felt;
+
+use crate::model::DojoModel;
+use crate::plugin::{DojoEntityDeleted, DojoEntityUpdated};
+
+/// System sets of the Dojo plugin, in the `Update` schedule.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DojoSet {
+    /// Polls the Torii and Starknet tasks, and emits the Dojo events.
+    Poll,
+    /// Synchronises the registered models with their components.
+    SyncModels,
+}
+
+/// The `entity_id` (hashed keys) of the Dojo entity mapped to a Bevy entity.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DojoEntityId(pub Felt);
+
+/// Mapping between the Dojo entities and the Bevy entities spawned for them.
+///
+/// Entries are removed when the Bevy entity is despawned, or when its
+/// `DojoEntityId` component is removed.
+#[derive(Resource, Debug, Default)]
+pub struct DojoEntityMap {
+    entities: HashMap<Felt, Entity>,
+    entity_ids: HashMap<Entity, Felt>,
+}
+
+impl DojoEntityMap {
+    /// Returns the Bevy entity of the Dojo entity.
+    pub fn get(&self, entity_id: Felt) -> Option<Entity> {
+        self.entities.get(&entity_id).copied()
+    }
+
+    /// Returns the `entity_id` of the Dojo entity mapped to the Bevy entity.
+    pub fn entity_id(&self, entity: Entity) -> Option<Felt> {
+        self.entity_ids.get(&entity).copied()
+    }
+
+    /// Returns true if a Bevy entity is mapped to the Dojo entity.
+    pub fn contains(&self, entity_id: Felt) -> bool {
+        self.entities.contains_key(&entity_id)
+    }
+
+    /// Iterates over the `entity_id` and the Bevy entity of every mapped Dojo entity.
+    pub fn iter(&self) -> impl Iterator<Item = (Felt, Entity)> + '_ {
+        self.entities.iter().map(|(id, e)| (*id, *e))
+    }
+
+    /// Returns the number of mapped Dojo entities.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns true if no Dojo entity is mapped.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Maps the Dojo entity to the Bevy entity, replacing their previous mappings.
+    ///
+    /// Registered models are then synchronised on this entity. For games spawning
+    /// the entities themselves, the entity must also hold the [`DojoEntityId`]
+    /// component, for the mapping to be removed when it is despawned.
+    pub fn insert(&mut self, entity_id: Felt, entity: Entity) {
+        let previous = self.entities.insert(entity_id, entity);
+        if let Some(previous) = previous.filter(|e| *e != entity) {
+            self.entity_ids.remove(&previous);
+        }
+
+        let previous = self.entity_ids.insert(entity, entity_id);
+        if let Some(previous) = previous.filter(|id| *id != entity_id) {
+            self.entities.remove(&previous);
+        }
+    }
+
+    fn remove_entity(&mut self, entity: Entity) {
+        let Some(entity_id) = self.entity_ids.remove(&entity) else {
+            return;
+        };
+
+        // The Dojo entity may already be mapped to a new Bevy entity.
+        if self.entities.get(&entity_id) == Some(&entity) {
+            self.entities.remove(&entity_id);
+        }
+    }
+}
+
+/// Removes the entity from the `DojoEntityMap` when it is despawned.
+pub(crate) fn on_dojo_entity_removed(
+    trigger: Trigger<OnRemove, DojoEntityId>,
+    mut entities: ResMut<DojoEntityMap>,
+) {
+    entities.remove_entity(trigger.target());
+}
+
+/// This event is emitted every time a registered model is updated by Torii.
+#[derive(Event, Debug, Clone)]
+pub struct DojoModelUpdated<T: DojoModel> {
+    pub entity_id: Felt,
+    pub value: T,
+}
+
+/// Registration of the Dojo models on the Bevy [`App`].
+pub trait DojoAppExt {
+    /// Registers the model `T`, to be inserted, updated and removed as a
+    /// component of the entity maintained for every Dojo entity.
+    ///
+    /// Every update of the model also emits a `DojoModelUpdated<T>` event.
+    ///
+    /// The `DojoPlugin` must be added before registering models.
+    fn register_dojo_model<T>(&mut self) -> &mut Self
+    where
+        T: DojoModel + Component + Clone;
+}
+
+impl DojoAppExt for App {
+    fn register_dojo_model<T>(&mut self) -> &mut Self
+    where
+        T: DojoModel + Component + Clone,
+    {
+        self.add_event::<DojoModelUpdated<T>>();
+        self.add_systems(
+            Update,
+            (remove_dojo_model::<T>, sync_dojo_model::<T>)
+                .chain()
+                .after(despawn_deleted_entities)
+                .in_set(DojoSet::SyncModels),
+        )
+    }
+}
+
+/// Despawns the entities deleted from the world, along with all their components.
+pub(crate) fn despawn_deleted_entities(
+    mut commands: Commands,
+    entities: Res<DojoEntityMap>,
+    mut ev_entity_deleted: EventReader<DojoEntityDeleted>,
+) {
+    for ev in ev_entity_deleted.read() {
+        if !ev.is_entity_deleted() {
+            continue;
+        }
+
+        let entity = entities.get(ev.entity_id);
+        if let Some(mut e) = entity.and_then(|e| commands.get_entity(e).ok()) {
+            debug!(entity_id = ?ev.entity_id, "Despawning deleted entity.");
+            e.try_despawn();
+        }
+    }
+}
+
+/// Removes the component `T` when the model has been deleted from the world.
+///
+/// Deleted entities are despawned by `despawn_deleted_entities`.
+fn remove_dojo_model<T>(
+    mut commands: Commands,
+    entities: Res<DojoEntityMap>,
+    mut ev_entity_deleted: EventReader<DojoEntityDeleted>,
+) where
+    T: DojoModel + Component,
+{
+    let tag = T::tag();
+
+    for ev in ev_entity_deleted.read() {
+        if ev.is_entity_deleted() || !ev.is_model_deleted(&tag) {
+            continue;
+        }
+
+        let entity = entities.get(ev.entity_id);
+        if let Some(mut e) = entity.and_then(|e| commands.get_entity(e).ok()) {
+            e.try_remove::<T>();
+        }
+    }
+}
+
+/// Inserts or updates the component `T` from the entity updates,
+/// and emits the `DojoModelUpdated<T>` event.
+///
+/// Models absent from an update are left untouched, since subscriptions
+/// only send the models that changed.
+fn sync_dojo_model<T>(
+    mut commands: Commands,
+    mut entities: ResMut<DojoEntityMap>,
+    mut ev_entity_updated: EventReader<DojoEntityUpdated>,
+    mut ev_model_updated: EventWriter<DojoModelUpdated<T>>,
+) where
+    T: DojoModel + Component + Clone,
+{
+    let tag = T::tag();
+
+    for ev in ev_entity_updated.read() {
+        let Some(model) = ev.models.iter().find(|m| m.name == tag) else {
+            continue;
+        };
+
+        let component = match T::from_struct(model) {
+            Ok(c) => c,
+            Err(e) => {
+                warn!(entity_id = ?ev.entity_id, "Invalid model {}: {}", tag, e);
+                continue;
+            }
+        };
+
+        ev_model_updated.write(DojoModelUpdated {
+            entity_id: ev.entity_id,
+            value: component.clone(),
+        });
+
+        // Despawned entities are removed from the map, a new entity is then spawned
+        // for an entity deleted earlier in the frame, or despawned by the game.
+        match entities.get(ev.entity_id) {
+            Some(entity) => {
+                commands.entity(entity).try_insert(component);
+            }
+            None => {
+                let entity = commands.spawn((DojoEntityId(ev.entity_id), component)).id();
+                entities.insert(ev.entity_id, entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DojoModel;
+    use crate::plugin::DojoPlugin;
+    use dojo_types::schema::Struct;
+
+    #[derive(Component, Debug, Clone, PartialEq, DojoModel)]
+    #[dojo(namespace = "di", name = "Position")]
+    struct Position {
+        #[dojo(key)]
+        player: Felt,
+        x: u32,
+    }
+
+    #[derive(Component, Debug, Clone, PartialEq, DojoModel)]
+    #[dojo(namespace = "di", name = "Moves")]
+    struct Moves {
+        #[dojo(key)]
+        player: Felt,
+        remaining: u8,
+    }
+
+    fn position(x: u32) -> Position {
+        Position {
+            player: Felt::ONE,
+            x,
+        }
+    }
+
+    fn moves(remaining: u8) -> Moves {
+        Moves {
+            player: Felt::ONE,
+            remaining,
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, DojoPlugin::default()));
+        app.register_dojo_model::<Position>()
+            .register_dojo_model::<Moves>();
+        app
+    }
+
+    fn updated(app: &mut App, entity_id: Felt, models: Vec<Struct>) {
+        app.world_mut()
+            .send_event(DojoEntityUpdated { entity_id, models });
+    }
+
+    fn deleted(app: &mut App, entity_id: Felt, models_removed: &[&str]) {
+        app.world_mut().send_event(DojoEntityDeleted {
+            entity_id,
+            models_removed: models_removed.iter().map(|m| m.to_string()).collect(),
+        });
+    }
+
+    fn component<T: Component + Clone>(app: &App, entity_id: Felt) -> Option<T> {
+        let entity = app.world().resource::<DojoEntityMap>().get(entity_id)?;
+        app.world().get::<T>(entity).cloned()
+    }
+
+    fn model_updates<T: DojoModel + Component + Clone>(app: &mut App) -> Vec<(Felt, T)> {
+        app.world_mut()
+            .resource_mut::<Events<DojoModelUpdated<T>>>()
+            .drain()
+            .map(|ev| (ev.entity_id, ev.value))
+            .collect()
+    }
+
+    #[test]
+    fn sync_models() {
+        let mut app = app();
+
+        // The entity is spawned on its first update.
+        updated(&mut app, Felt::ONE, vec![position(1).to_struct()]);
+        app.update();
+
+        let entity = app
+            .world()
+            .resource::<DojoEntityMap>()
+            .get(Felt::ONE)
+            .unwrap();
+        assert_eq!(
+            app.world().get::<DojoEntityId>(entity),
+            Some(&DojoEntityId(Felt::ONE))
+        );
+        assert_eq!(component(&app, Felt::ONE), Some(position(1)));
+        assert_eq!(component::<Moves>(&app, Felt::ONE), None);
+        assert_eq!(model_updates(&mut app), [(Felt::ONE, position(1))]);
+
+        // The entity is updated in place.
+        updated(
+            &mut app,
+            Felt::ONE,
+            vec![position(2).to_struct(), moves(3).to_struct()],
+        );
+        app.update();
+
+        assert_eq!(
+            app.world().resource::<DojoEntityMap>().get(Felt::ONE),
+            Some(entity)
+        );
+        assert_eq!(component(&app, Felt::ONE), Some(position(2)));
+        assert_eq!(component(&app, Felt::ONE), Some(moves(3)));
+        assert_eq!(model_updates(&mut app), [(Felt::ONE, moves(3))]);
+
+        // A deleted model is removed from the entity.
+        deleted(&mut app, Felt::ONE, &["di-Moves"]);
+        app.update();
+
+        assert_eq!(component(&app, Felt::ONE), Some(position(2)));
+        assert_eq!(component::<Moves>(&app, Felt::ONE), None);
+
+        // A deleted entity is despawned.
+        deleted(&mut app, Felt::ONE, &[]);
+        app.update();
+
+        assert!(app.world().get_entity(entity).is_err());
+        assert!(app.world().resource::<DojoEntityMap>().is_empty());
+    }
+
+    #[test]
+    fn sync_entity_deleted_and_updated() {
+        let mut app = app();
+
+        updated(
+            &mut app,
+            Felt::ONE,
+            vec![position(1).to_struct(), moves(3).to_struct()],
+        );
+        app.update();
+        let old = app
+            .world()
+            .resource::<DojoEntityMap>()
+            .get(Felt::ONE)
+            .unwrap();
+
+        // The deletion is applied first, a new entity is spawned for the update.
+        deleted(&mut app, Felt::ONE, &[]);
+        updated(&mut app, Felt::ONE, vec![position(5).to_struct()]);
+        app.update();
+
+        let entities = app.world().resource::<DojoEntityMap>();
+        let new = entities.get(Felt::ONE).unwrap();
+        assert_ne!(new, old);
+        assert_eq!(entities.len(), 1);
+        assert!(app.world().get_entity(old).is_err());
+        assert_eq!(component(&app, Felt::ONE), Some(position(5)));
+        assert_eq!(component::<Moves>(&app, Felt::ONE), None);
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<DojoEntityMap>();
+        world.add_observer(on_dojo_entity_removed);
+        world
+    }
+
+    fn spawn(world: &mut World, entity_id: Felt) -> Entity {
+        let entity = world.spawn(DojoEntityId(entity_id)).id();
+        world
+            .resource_mut::<DojoEntityMap>()
+            .insert(entity_id, entity);
+        entity
+    }
+
+    #[test]
+    fn entity_map_lookups() {
+        let mut world = world();
+        let first = spawn(&mut world, Felt::ONE);
+        let second = spawn(&mut world, Felt::TWO);
+
+        let entities = world.resource::<DojoEntityMap>();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities.get(Felt::ONE), Some(first));
+        assert_eq!(entities.entity_id(second), Some(Felt::TWO));
+        assert!(!entities.contains(Felt::THREE));
+
+        let mut mapped: Vec<_> = entities.iter().collect();
+        mapped.sort_by_key(|(id, _)| *id);
+        assert_eq!(mapped, [(Felt::ONE, first), (Felt::TWO, second)]);
+    }
+
+    #[test]
+    fn entity_map_despawned_entity() {
+        let mut world = world();
+        let entity = spawn(&mut world, Felt::ONE);
+
+        world.despawn(entity);
+
+        let entities = world.resource::<DojoEntityMap>();
+        assert!(entities.is_empty());
+        assert_eq!(entities.entity_id(entity), None);
+    }
+
+    #[test]
+    fn entity_map_removed_entity_id() {
+        let mut world = world();
+        let entity = spawn(&mut world, Felt::ONE);
+
+        world.entity_mut(entity).remove::<DojoEntityId>();
+
+        assert!(world.get_entity(entity).is_ok());
+        assert!(world.resource::<DojoEntityMap>().is_empty());
+    }
+
+    #[test]
+    fn entity_map_remapped_entity() {
+        let mut world = world();
+        let old = spawn(&mut world, Felt::ONE);
+        let new = spawn(&mut world, Felt::ONE);
+
+        // Despawning the old entity keeps the mapping to the new one.
+        world.despawn(old);
+
+        let entities = world.resource::<DojoEntityMap>();
+        assert_eq!(entities.get(Felt::ONE), Some(new));
+        assert_eq!(entities.entity_id(new), Some(Felt::ONE));
+        assert_eq!(entities.len(), 1);
+    }
+
+    #[test]
+    fn entity_map_insert_replaces_mappings() {
+        let mut world = world();
+        let old = spawn(&mut world, Felt::ONE);
+        let entity = spawn(&mut world, Felt::TWO);
+
+        // The entity is mapped to another Dojo entity, replacing the old entity.
+        world
+            .resource_mut::<DojoEntityMap>()
+            .insert(Felt::ONE, entity);
+
+        let entities = world.resource::<DojoEntityMap>();
+        assert_eq!(entities.get(Felt::ONE), Some(entity));
+        assert_eq!(entities.entity_id(entity), Some(Felt::ONE));
+        assert_eq!(entities.entity_id(old), None);
+        assert!(!entities.contains(Felt::TWO));
+        assert_eq!(entities.len(), 1);
+    }
+}
@@ -0,0 +1,324 @@
+//! Systems calls.
+//!
+//! A [`DojoSystem`] identifies a system by the tag of its contract and its name,
+//! along with its arguments. The address of the contract is resolved from the
+//! manifest when the system is called, and the arguments are serialized with
+//! the Cairo serialization of their Dojo type.
+
+use dojo_types::primitive::Primitive;
+use dojo_types::schema::Ty;
+use starknet::core::types::{Call, Felt};
+use starknet::core::utils::get_selector_from_name;
+use std::collections::HashMap;
+
+use crate::bindgen::{AbiType, CairoType, abi_types, maybe_core_type, resolve_type, short_name};
+use crate::error::{DojoError, DojoModelError};
+use crate::manifest::DojoManifest;
+use crate::model::DojoType;
+
+/// A call to a system of a contract registered in the world.
+#[derive(Debug, Clone)]
+pub struct DojoSystem {
+    /// The tag of the contract, `<namespace>-<name>`.
+    pub contract_tag: String,
+    /// The name of the system.
+    pub name: String,
+    /// The arguments of the system, in order.
+    pub args: Vec<Ty>,
+}
+
+impl DojoSystem {
+    /// Builds a system from the tuple of its arguments, `()` if it has none.
+    ///
+    /// A single argument can be given without a tuple, unless the argument is a tuple itself.
+    pub fn new<A: DojoType>(
+        contract_tag: impl Into<String>,
+        name: impl Into<String>,
+        args: A,
+    ) -> Self {
+        let args = match args.to_ty() {
+            Ty::Tuple(items) => items,
+            ty => vec![ty],
+        };
+
+        Self {
+            contract_tag: contract_tag.into(),
+            name: name.into(),
+            args,
+        }
+    }
+
+    /// Returns the serialized arguments.
+    pub fn calldata(&self) -> Result<Vec<Felt>, DojoModelError> {
+        let mut calldata = vec![];
+
+        for arg in &self.args {
+            calldata.extend(
+                arg.serialize()
+                    .map_err(|e| DojoModelError::Serialization(e.to_string()))?,
+            );
+        }
+
+        Ok(calldata)
+    }
+
+    /// Builds the call, with the address of the contract resolved from the manifest.
+    ///
+    /// The arguments are checked against the ABI of the contract, if available.
+    pub fn to_call(&self, manifest: &DojoManifest) -> Result<Call, DojoError> {
+        let not_found = || DojoError::SystemNotFound {
+            contract: self.contract_tag.clone(),
+            system: self.name.clone(),
+        };
+
+        let contract = manifest
+            .contract(&self.contract_tag)
+            .ok_or_else(|| DojoError::ContractNotFound(self.contract_tag.clone()))?;
+
+        match contract.abi_system(&self.name) {
+            Some(system) if system.inputs.len() != self.args.len() => {
+                return Err(DojoError::SystemArguments {
+                    system: self.name.clone(),
+                    expected: system.inputs.len(),
+                    actual: self.args.len(),
+                });
+            }
+            Some(system) => {
+                let abi_types = abi_types(&contract.abi);
+
+                for (input, arg) in system.inputs.iter().zip(&self.args) {
+                    if !matches_declared_type(arg, &input.ty, &abi_types) {
+                        return Err(DojoError::SystemArgumentType {
+                            system: self.name.clone(),
+                            argument: input.name.clone(),
+                            expected: input.ty.clone(),
+                            found: arg.name(),
+                        });
+                    }
+                }
+            }
+            None if contract.has_system(&self.name) => {}
+            None => return Err(not_found()),
+        }
+
+        Ok(Call {
+            to: contract.address,
+            selector: get_selector_from_name(&self.name).map_err(|_| not_found())?,
+            calldata: self.calldata()?,
+        })
+    }
+}
+
+/// Returns true if the value matches the Cairo type declared in the ABI.
+///
+/// Types that can't be parsed are not checked.
+fn matches_declared_type(ty: &Ty, declared: &str, abi_types: &HashMap<String, AbiType>) -> bool {
+    CairoType::parse(declared).map_or(true, |cairo_type| matches_type(ty, &cairo_type, abi_types))
+}
+
+/// Returns true if the value matches the Cairo type, with the structs and enums
+/// resolved from the ABI of the contract.
+///
+/// Members and variants are matched by position and type, not by name, since the
+/// Rust names may differ from the Cairo ones. Types that can't be resolved are not checked.
+fn matches_type(ty: &Ty, cairo_type: &CairoType, abi_types: &HashMap<String, AbiType>) -> bool {
+    let (path, args) = match cairo_type {
+        CairoType::Path { path, args } => (path, args),
+        CairoType::Tuple(items) => {
+            return match ty {
+                Ty::Tuple(values) => {
+                    values.len() == items.len()
+                        && values
+                            .iter()
+                            .zip(items)
+                            .all(|(v, i)| matches_type(v, i, abi_types))
+                }
+                _ => false,
+            };
+        }
+    };
+
+    if maybe_core_type(path) {
+        let matched = match (short_name(path), args.as_slice(), ty) {
+            ("u8", [], Ty::Primitive(Primitive::U8(_)))
+            | ("u16", [], Ty::Primitive(Primitive::U16(_)))
+            | ("u32", [], Ty::Primitive(Primitive::U32(_)))
+            | ("u64", [], Ty::Primitive(Primitive::U64(_)))
+            | ("u128", [], Ty::Primitive(Primitive::U128(_)))
+            | ("u256", [], Ty::Primitive(Primitive::U256(_)))
+            | ("i8", [], Ty::Primitive(Primitive::I8(_)))
+            | ("i16", [], Ty::Primitive(Primitive::I16(_)))
+            | ("i32", [], Ty::Primitive(Primitive::I32(_)))
+            | ("i64", [], Ty::Primitive(Primitive::I64(_)))
+            | ("i128", [], Ty::Primitive(Primitive::I128(_)))
+            | ("bool", [], Ty::Primitive(Primitive::Bool(_)))
+            | ("ByteArray", [], Ty::ByteArray(_)) => Some(true),
+            (
+                "felt252" | "ContractAddress" | "ClassHash" | "EthAddress",
+                [],
+                Ty::Primitive(
+                    Primitive::Felt252(_)
+                    | Primitive::ContractAddress(_)
+                    | Primitive::ClassHash(_)
+                    | Primitive::EthAddress(_),
+                ),
+            ) => Some(true),
+            ("Array" | "Span", [item], Ty::Array(values)) => {
+                Some(values.iter().all(|v| matches_type(v, item, abi_types)))
+            }
+            ("Option", [item], Ty::Enum(e)) => {
+                let option = e.option.and_then(|o| e.options.get(o as usize));
+                Some(match option {
+                    Some(o) if o.name == "Some" => matches_type(&o.ty, item, abi_types),
+                    Some(o) => o.name == "None",
+                    None => false,
+                })
+            }
+            (
+                "u8" | "u16" | "u32" | "u64" | "u128" | "u256" | "i8" | "i16" | "i32" | "i64"
+                | "i128" | "bool" | "ByteArray" | "felt252" | "ContractAddress" | "ClassHash"
+                | "EthAddress" | "Array" | "Span" | "Option",
+                _,
+                _,
+            ) => Some(false),
+            // The other core types are not mapped by the plugin.
+            _ if path.starts_with("core::") => Some(true),
+            _ => None,
+        };
+
+        if let Some(matched) = matched {
+            return matched;
+        }
+    }
+
+    let abi_type = resolve_type(abi_types, path)
+        .ok()
+        .and_then(|p| abi_types.get(&p));
+
+    match (abi_type, ty) {
+        (Some(AbiType::Struct(fields)), Ty::Struct(s)) => {
+            s.children.len() == fields.len()
+                && s.children
+                    .iter()
+                    .zip(fields)
+                    .all(|(m, (_, field_ty))| matches_declared_type(&m.ty, field_ty, abi_types))
+        }
+        (Some(AbiType::Enum(variants)), Ty::Enum(e)) => {
+            let option = e.option.map(usize::from);
+            match option.and_then(|o| e.options.get(o).zip(variants.get(o))) {
+                Some((option, (_, variant_ty))) => {
+                    matches_declared_type(&option.ty, variant_ty, abi_types)
+                }
+                None => false,
+            }
+        }
+        (Some(_), _) => false,
+        (None, ty) => matches!(ty, Ty::Struct(_) | Ty::Enum(_)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DojoType;
+    use crate::manifest::TEST_MANIFEST;
+
+    #[derive(DojoType)]
+    enum Direction {
+        Left,
+        Jump(Vec2),
+    }
+
+    #[derive(DojoType)]
+    struct Vec2 {
+        x: u32,
+        y: u32,
+    }
+
+    #[derive(DojoType)]
+    struct Vec3 {
+        x: u32,
+        y: u32,
+        z: u32,
+    }
+
+    fn to_call<A: DojoType>(args: A) -> Result<Call, DojoError> {
+        let manifest = DojoManifest::from_json(TEST_MANIFEST).unwrap();
+        DojoSystem::new("di-actions", "move", args).to_call(&manifest)
+    }
+
+    #[test]
+    fn to_call_with_matching_arguments() {
+        let call = to_call((Direction::Jump(Vec2 { x: 1, y: 2 }), vec![(3_u8, Felt::TWO)])).unwrap();
+
+        assert_eq!(call.to, Felt::THREE);
+        assert_eq!(call.selector, get_selector_from_name("move").unwrap());
+        assert_eq!(
+            call.calldata,
+            [
+                Felt::ONE,
+                Felt::ONE,
+                Felt::TWO,
+                Felt::ONE,
+                Felt::THREE,
+                Felt::TWO,
+            ]
+        );
+
+        assert!(to_call((Direction::Left, Vec::<(u8, Felt)>::new())).is_ok());
+    }
+
+    #[test]
+    fn to_call_argument_count() {
+        assert!(matches!(
+            to_call(Direction::Left),
+            Err(DojoError::SystemArguments {
+                expected: 2,
+                actual: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn to_call_argument_type() {
+        assert!(matches!(
+            to_call((Direction::Left, vec![(3_u32, Felt::TWO)])),
+            Err(DojoError::SystemArgumentType { argument, .. }) if argument == "path"
+        ));
+
+        assert!(matches!(
+            to_call((Vec2 { x: 1, y: 2 }, vec![(3_u8, Felt::TWO)])),
+            Err(DojoError::SystemArgumentType { argument, .. }) if argument == "direction"
+        ));
+
+        #[derive(DojoType)]
+        enum OtherDirection {
+            Left,
+            Jump(Vec3),
+        }
+
+        assert!(to_call((OtherDirection::Left, vec![(3_u8, Felt::TWO)])).is_ok());
+        assert!(matches!(
+            to_call((
+                OtherDirection::Jump(Vec3 { x: 1, y: 2, z: 3 }),
+                vec![(3_u8, Felt::TWO)],
+            )),
+            Err(DojoError::SystemArgumentType { argument, .. }) if argument == "direction"
+        ));
+    }
+
+    #[test]
+    fn to_call_unknown_system() {
+        let manifest = DojoManifest::from_json(TEST_MANIFEST).unwrap();
+
+        assert!(matches!(
+            DojoSystem::new("di-actions", "spawn", ()).to_call(&manifest),
+            Err(DojoError::SystemNotFound { .. })
+        ));
+        assert!(matches!(
+            DojoSystem::new("di-other", "spawn", ()).to_call(&manifest),
+            Err(DojoError::ContractNotFound(_))
+        ));
+    }
+}
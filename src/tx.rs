@@ -0,0 +1,840 @@
+//! Transactions lifecycle.
+//!
+//! Transactions are queued from Bevy systems and executed in the background
+//! on the tokio runtime. Every step of the lifecycle is reported back to the
+//! main thread through a channel, to be emitted as Bevy events by the Dojo plugin.
+//!
+//! The nonce of the account is tracked locally, so transactions queued back-to-back
+//! are sent with sequential nonces instead of all fetching the same one from the node.
+//! The tasks take their nonce in the order the transactions were queued.
+//!
+//! With [`TxBatching`], the transactions queued close together are coalesced into a
+//! single multicall, whose lifecycle is reported to every transaction of the batch.
+
+use bevy::prelude::*;
+use starknet::accounts::{Account, AccountError, ConnectedAccount, ExecutionV3};
+use starknet::core::types::{
+    Call, Event as StarknetEvent, ExecutionResult, FeeEstimate, FeePayment, Felt, StarknetError,
+    TransactionExecutionStatus, TransactionFinalityStatus, TransactionReceipt,
+};
+use starknet::providers::{Provider, ProviderError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{Mutex, oneshot};
+
+use crate::account::DojoAccount;
+
+/// Configuration of the transactions execution.
+#[derive(Debug, Clone, Copy)]
+pub struct TxConfig {
+    /// Interval between two `get_transaction_receipt` requests.
+    pub receipt_poll_interval: Duration,
+    /// Time after which a transaction without receipt is considered failed.
+    pub receipt_timeout: Duration,
+    /// Default fee options of the transactions.
+    pub fee: TxFeeOptions,
+    /// Sends the transactions one at a time, in the order they were queued (default).
+    ///
+    /// Nonces are always attributed in the order the transactions were queued, but
+    /// concurrent sends may reach the node out of order. Receipts are still awaited
+    /// concurrently.
+    ///
+    /// Without serialized sends, a transaction failing before being sent leaves
+    /// a gap in the nonces: the transactions already sent after it with the following
+    /// nonces are never executed, and are reported as failed once their receipt times out.
+    pub serialize_sends: bool,
+    /// Coalescing of the transactions queued with `DojoResource::queue_tx`.
+    pub batching: TxBatching,
+}
+
+impl Default for TxConfig {
+    fn default() -> Self {
+        Self {
+            receipt_poll_interval: Duration::from_millis(500),
+            receipt_timeout: Duration::from_secs(60),
+            fee: TxFeeOptions::default(),
+            serialize_sends: true,
+            batching: TxBatching::Disabled,
+        }
+    }
+}
+
+/// Coalescing of the queued transactions into a single multicall.
+///
+/// A multicall is executed atomically: if one of the calls reverts, the whole
+/// batch is reverted and every transaction of the batch is reported as reverted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TxBatching {
+    /// Every transaction is sent on its own.
+    #[default]
+    Disabled,
+    /// The transactions queued between two polls of the plugin, that is within
+    /// a frame, are sent as a single multicall.
+    Frame,
+    /// The transactions queued within the window, starting with the first
+    /// transaction of the batch, are sent as a single multicall.
+    Window(Duration),
+}
+
+/// Transactions waiting to be sent as a single multicall.
+#[derive(Debug, Default)]
+pub(crate) struct TxBatch {
+    ids: Vec<DojoTxId>,
+    calls: Vec<Call>,
+    started_at: Option<Instant>,
+}
+
+impl TxBatch {
+    /// Adds the calls of the transaction to the batch.
+    pub(crate) fn push(&mut self, id: DojoTxId, calls: Vec<Call>) {
+        self.started_at.get_or_insert_with(Instant::now);
+        self.ids.push(id);
+        self.calls.extend(calls);
+    }
+
+    /// Returns true if the batch must be sent.
+    ///
+    /// A pending batch is always due once batching is disabled.
+    pub(crate) fn is_due(&self, batching: TxBatching) -> bool {
+        match (batching, self.started_at) {
+            (_, None) => false,
+            (TxBatching::Window(window), Some(started_at)) => started_at.elapsed() >= window,
+            (TxBatching::Disabled | TxBatching::Frame, Some(_)) => true,
+        }
+    }
+
+    /// Takes the ids and the calls of the batched transactions, emptying the batch.
+    pub(crate) fn take(&mut self) -> (Vec<DojoTxId>, Vec<Call>) {
+        self.started_at = None;
+        (
+            std::mem::take(&mut self.ids),
+            std::mem::take(&mut self.calls),
+        )
+    }
+
+    /// Returns the number of batched transactions.
+    pub(crate) fn len(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+/// Fee options of a transaction.
+///
+/// The resource bounds that are not set are estimated by starknet-rs when the
+/// transaction is sent, increased by the multipliers (1.5 if not set).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TxFeeOptions {
+    pub l1_gas: Option<u64>,
+    pub l1_gas_price: Option<u128>,
+    pub l2_gas: Option<u64>,
+    pub l2_gas_price: Option<u128>,
+    pub l1_data_gas: Option<u64>,
+    pub l1_data_gas_price: Option<u128>,
+    pub tip: Option<u64>,
+    pub gas_estimate_multiplier: Option<f64>,
+    pub gas_price_estimate_multiplier: Option<f64>,
+    /// Estimates the fee before sending the transaction, to emit the `DojoTxFeeEstimated`
+    /// event. The estimate is then used to send the transaction.
+    pub estimate_fee: bool,
+}
+
+impl TxFeeOptions {
+    /// Fills the resource bounds that are not set from the fee estimate.
+    pub fn with_estimate(self, estimate: &FeeEstimate) -> Self {
+        let gas = |v: u64| (v as f64 * self.gas_estimate_multiplier.unwrap_or(1.5)) as u64;
+        let price =
+            |v: u128| (v as f64 * self.gas_price_estimate_multiplier.unwrap_or(1.5)) as u128;
+
+        Self {
+            l1_gas: self.l1_gas.or(Some(gas(estimate.l1_gas_consumed))),
+            l1_gas_price: self.l1_gas_price.or(Some(price(estimate.l1_gas_price))),
+            l2_gas: self.l2_gas.or(Some(gas(estimate.l2_gas_consumed))),
+            l2_gas_price: self.l2_gas_price.or(Some(price(estimate.l2_gas_price))),
+            l1_data_gas: self
+                .l1_data_gas
+                .or(Some(gas(estimate.l1_data_gas_consumed))),
+            l1_data_gas_price: self
+                .l1_data_gas_price
+                .or(Some(price(estimate.l1_data_gas_price))),
+            ..self
+        }
+    }
+
+    /// Applies the options to the execution.
+    fn apply<'a, A: Account>(&self, mut execution: ExecutionV3<'a, A>) -> ExecutionV3<'a, A> {
+        if let Some(v) = self.l1_gas {
+            execution = execution.l1_gas(v);
+        }
+        if let Some(v) = self.l1_gas_price {
+            execution = execution.l1_gas_price(v);
+        }
+        if let Some(v) = self.l2_gas {
+            execution = execution.l2_gas(v);
+        }
+        if let Some(v) = self.l2_gas_price {
+            execution = execution.l2_gas_price(v);
+        }
+        if let Some(v) = self.l1_data_gas {
+            execution = execution.l1_data_gas(v);
+        }
+        if let Some(v) = self.l1_data_gas_price {
+            execution = execution.l1_data_gas_price(v);
+        }
+        if let Some(v) = self.tip {
+            execution = execution.tip(v);
+        }
+        if let Some(v) = self.gas_estimate_multiplier {
+            execution = execution.gas_estimate_multiplier(v);
+        }
+        if let Some(v) = self.gas_price_estimate_multiplier {
+            execution = execution.gas_price_estimate_multiplier(v);
+        }
+        execution
+    }
+}
+
+/// Receipt of an executed transaction.
+#[derive(Debug, Clone)]
+pub struct DojoTxReceipt {
+    pub execution_status: TransactionExecutionStatus,
+    pub revert_reason: Option<String>,
+    pub finality_status: TransactionFinalityStatus,
+    pub actual_fee: FeePayment,
+    pub events: Vec<StarknetEvent>,
+}
+
+impl From<TransactionReceipt> for DojoTxReceipt {
+    fn from(receipt: TransactionReceipt) -> Self {
+        let (execution_result, finality_status, actual_fee, events) = match receipt {
+            TransactionReceipt::Invoke(r) => (
+                r.execution_result,
+                r.finality_status,
+                r.actual_fee,
+                r.events,
+            ),
+            TransactionReceipt::L1Handler(r) => (
+                r.execution_result,
+                r.finality_status,
+                r.actual_fee,
+                r.events,
+            ),
+            TransactionReceipt::Declare(r) => (
+                r.execution_result,
+                r.finality_status,
+                r.actual_fee,
+                r.events,
+            ),
+            TransactionReceipt::Deploy(r) => (
+                r.execution_result,
+                r.finality_status,
+                r.actual_fee,
+                r.events,
+            ),
+            TransactionReceipt::DeployAccount(r) => (
+                r.execution_result,
+                r.finality_status,
+                r.actual_fee,
+                r.events,
+            ),
+        };
+
+        let (execution_status, revert_reason) = match execution_result {
+            ExecutionResult::Succeeded => (TransactionExecutionStatus::Succeeded, None),
+            ExecutionResult::Reverted { reason } => {
+                (TransactionExecutionStatus::Reverted, Some(reason))
+            }
+        };
+
+        Self {
+            execution_status,
+            revert_reason,
+            finality_status,
+            actual_fee,
+            events,
+        }
+    }
+}
+
+/// Identifier of a transaction queued with `DojoResource::queue_tx`.
+///
+/// Ids are attributed sequentially, and are used to correlate the
+/// transaction lifecycle events with the request that queued it.
+/// The transactions sent in the same batch share their transaction hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DojoTxId(pub u64);
+
+/// This event is emitted when the fee of a transaction has been estimated.
+///
+/// Only emitted for the transactions queued with `TxFeeOptions::estimate_fee`.
+/// For a batch, the estimate is the one of the whole multicall.
+#[derive(Event, Debug)]
+pub struct DojoTxFeeEstimated {
+    pub id: DojoTxId,
+    pub estimate: FeeEstimate,
+}
+
+/// This event is emitted when a transaction has been sent to the Starknet node.
+#[derive(Event, Debug)]
+pub struct DojoTxSubmitted {
+    pub id: DojoTxId,
+    pub transaction_hash: Felt,
+}
+
+/// This event is emitted when the receipt of a transaction reports a successful execution.
+#[derive(Event, Debug)]
+pub struct DojoTxAccepted {
+    pub id: DojoTxId,
+    pub transaction_hash: Felt,
+    pub receipt: DojoTxReceipt,
+}
+
+/// This event is emitted when the execution of a transaction reverted.
+///
+/// The transaction hash and the receipt are `None` if the transaction reverted
+/// during the fee estimation, and was therefore never sent.
+#[derive(Event, Debug)]
+pub struct DojoTxReverted {
+    pub id: DojoTxId,
+    pub transaction_hash: Option<Felt>,
+    pub reason: String,
+    pub receipt: Option<DojoTxReceipt>,
+}
+
+/// This event is emitted when a transaction could not be executed for
+/// any other reason than a revert (no account, signing or network error...).
+#[derive(Event, Debug)]
+pub struct DojoTxFailed {
+    pub id: DojoTxId,
+    pub transaction_hash: Option<Felt>,
+    pub error: String,
+}
+
+/// Identifier of a fee estimation queued with `DojoResource::queue_fee_estimate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DojoFeeEstimateId(pub u64);
+
+/// This event is emitted when the fee of calls queued with
+/// `DojoResource::queue_fee_estimate` has been estimated.
+#[derive(Event, Debug)]
+pub struct DojoFeeEstimated {
+    pub id: DojoFeeEstimateId,
+    pub estimate: FeeEstimate,
+}
+
+/// This event is emitted when the fee of calls queued with
+/// `DojoResource::queue_fee_estimate` could not be estimated,
+/// the calls reverting or the node being unreachable.
+#[derive(Event, Debug)]
+pub struct DojoFeeEstimateFailed {
+    pub id: DojoFeeEstimateId,
+    pub error: String,
+}
+
+/// Progress of a transaction, sent from the background task to the plugin.
+#[derive(Debug, Clone)]
+pub(crate) enum TxUpdate {
+    FeeEstimated {
+        estimate: FeeEstimate,
+    },
+    Submitted {
+        transaction_hash: Felt,
+    },
+    Accepted {
+        transaction_hash: Felt,
+        receipt: DojoTxReceipt,
+    },
+    Reverted {
+        transaction_hash: Option<Felt>,
+        reason: String,
+        receipt: Option<DojoTxReceipt>,
+    },
+    Failed {
+        transaction_hash: Option<Felt>,
+        error: String,
+    },
+}
+
+/// Next nonce of the account, shared by the transactions tasks.
+///
+/// The nonce is fetched from the node when unknown, which is the case for the
+/// first transaction and after the last transaction failed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NonceTracker(Arc<Mutex<Option<Felt>>>);
+
+impl NonceTracker {
+    /// Hands out the next nonce, fetched with `fetch` if unknown.
+    async fn take<E>(&self, fetch: impl Future<Output = Result<Felt, E>>) -> Result<Felt, E> {
+        let mut next = self.0.lock().await;
+
+        let nonce = match *next {
+            Some(nonce) => nonce,
+            None => fetch.await?,
+        };
+
+        *next = Some(nonce + Felt::ONE);
+        Ok(nonce)
+    }
+
+    /// Forgets the tracked nonce after the transaction using `nonce` failed,
+    /// to fetch it again from the node for the next transaction.
+    ///
+    /// The nonce is kept if a later nonce has already been handed out, since it
+    /// would be handed out twice: the transactions using the later nonces can't be
+    /// executed after the gap and fail in turn, the last one resyncing the nonce.
+    async fn resync(&self, nonce: Felt) {
+        let mut next = self.0.lock().await;
+
+        if *next == Some(nonce + Felt::ONE) {
+            *next = None;
+        }
+    }
+}
+
+/// Turns of the transactions tasks, handed out in the order the transactions are queued.
+///
+/// A task takes its nonce once the task queued before it has ended its turn,
+/// which is after taking its own nonce, or after sending its transaction with
+/// `TxConfig::serialize_sends`.
+#[derive(Debug, Default)]
+pub(crate) struct TxTurns {
+    last: Option<oneshot::Receiver<()>>,
+}
+
+impl TxTurns {
+    /// Returns the turn of the next transaction task.
+    ///
+    /// Must be called when the task is spawned, to follow the queue order.
+    pub(crate) fn turn(&mut self) -> TxTurn {
+        let (done, last) = oneshot::channel();
+
+        TxTurn {
+            previous: self.last.replace(last),
+            _done: done,
+        }
+    }
+}
+
+/// Turn of a transaction task, ended when dropped.
+#[derive(Debug)]
+pub(crate) struct TxTurn {
+    previous: Option<oneshot::Receiver<()>>,
+    _done: oneshot::Sender<()>,
+}
+
+impl TxTurn {
+    /// Waits for the previous task to end its turn.
+    async fn wait(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            // Nothing is ever sent, the channel is closed when the turn is dropped.
+            let _ = previous.await;
+        }
+    }
+}
+
+/// Sender used by the transactions tasks to report their progress.
+pub(crate) type TxUpdateSender = UnboundedSender<(DojoTxId, TxUpdate)>;
+
+/// Executes the calls with the given account, reporting every step to `updates`
+/// for each of the `ids` (several for a batch of transactions).
+///
+/// The nonce is taken during the turn of the task. Once sent, the transaction receipt is polled until the transaction
+/// is executed or the configured timeout is reached.
+pub(crate) async fn execute_tx(
+    account: &DojoAccount,
+    ids: Vec<DojoTxId>,
+    calls: Vec<Call>,
+    config: TxConfig,
+    nonces: NonceTracker,
+    mut turn: TxTurn,
+    updates: TxUpdateSender,
+) {
+    let Some(calls) = sender_calls(account, calls, &ids, &updates).await else {
+        return;
+    };
+    let account = account.sender();
+
+    turn.wait().await;
+
+    let nonce = match nonces.take(account.get_nonce()).await {
+        Ok(nonce) => nonce,
+        Err(e) => {
+            let update = TxUpdate::Failed {
+                transaction_hash: None,
+                error: format!("Failed to fetch the nonce: {}", e),
+            };
+            report(&updates, &ids, update);
+            return;
+        }
+    };
+
+    // The turn is held until the transaction is sent to serialize the sends.
+    let turn = config.serialize_sends.then_some(turn);
+
+    let mut fee = config.fee;
+
+    if fee.estimate_fee {
+        let estimate = estimate_fee(account, &ids, calls.clone(), fee, nonce, &updates).await;
+        let Some(estimate) = estimate else {
+            nonces.resync(nonce).await;
+            return;
+        };
+
+        fee = fee.with_estimate(&estimate);
+    }
+
+    let execution = fee.apply(account.execute_v3(calls)).nonce(nonce);
+
+    let transaction_hash = match execution.send().await {
+        Ok(result) => result.transaction_hash,
+        Err(e) => {
+            report(&updates, &ids, not_sent(&e));
+            nonces.resync(nonce).await;
+            return;
+        }
+    };
+
+    drop(turn);
+
+    report(&updates, &ids, TxUpdate::Submitted { transaction_hash });
+
+    let update = match wait_for_receipt(account.provider(), transaction_hash, config).await {
+        Ok(receipt) => {
+            let receipt = DojoTxReceipt::from(receipt);
+
+            match receipt.revert_reason.clone() {
+                None => TxUpdate::Accepted {
+                    transaction_hash,
+                    receipt,
+                },
+                Some(reason) => TxUpdate::Reverted {
+                    transaction_hash: Some(transaction_hash),
+                    reason,
+                    receipt: Some(receipt),
+                },
+            }
+        }
+        Err(error) => {
+            // The transaction may have been dropped by the node, along with its nonce.
+            nonces.resync(nonce).await;
+
+            TxUpdate::Failed {
+                transaction_hash: Some(transaction_hash),
+                error,
+            }
+        }
+    };
+
+    report(&updates, &ids, update);
+}
+
+/// Reports the update to every transaction executed by the task.
+fn report(updates: &TxUpdateSender, ids: &[DojoTxId], update: TxUpdate) {
+    for id in ids {
+        let _ = updates.send((*id, update.clone()));
+    }
+}
+
+/// Estimates the fee of the calls with the given account, without sending them.
+///
+/// The nonce of the account is fetched from the node.
+pub(crate) async fn estimate_calls_fee(
+    account: &DojoAccount,
+    calls: Vec<Call>,
+    fee: TxFeeOptions,
+) -> Result<FeeEstimate, String> {
+    let calls = account
+        .sender_calls(calls)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    fee.apply(account.sender().execute_v3(calls))
+        .estimate_fee()
+        .await
+        .map_err(|e| match revert_reason(&e) {
+            Some(reason) => format!("Execution reverted: {}", reason),
+            None => e.to_string(),
+        })
+}
+
+/// Returns the calls sent by the sender of the account, or reports the
+/// transactions as failed if the calls can't be executed by the account.
+async fn sender_calls(
+    account: &DojoAccount,
+    calls: Vec<Call>,
+    ids: &[DojoTxId],
+    updates: &TxUpdateSender,
+) -> Option<Vec<Call>> {
+    match account.sender_calls(calls).await {
+        Ok(calls) => Some(calls),
+        Err(e) => {
+            let update = TxUpdate::Failed {
+                transaction_hash: None,
+                error: e.to_string(),
+            };
+            report(updates, ids, update);
+            None
+        }
+    }
+}
+
+/// Estimates the fee of the transaction, reporting the estimate or the failure to `updates`.
+async fn estimate_fee<A>(
+    account: &A,
+    ids: &[DojoTxId],
+    calls: Vec<Call>,
+    fee: TxFeeOptions,
+    nonce: Felt,
+    updates: &TxUpdateSender,
+) -> Option<FeeEstimate>
+where
+    A: ConnectedAccount + Sync,
+{
+    let execution = fee.apply(account.execute_v3(calls)).nonce(nonce);
+
+    match execution.estimate_fee().await {
+        Ok(estimate) => {
+            let update = TxUpdate::FeeEstimated {
+                estimate: estimate.clone(),
+            };
+            report(updates, ids, update);
+            Some(estimate)
+        }
+        Err(e) => {
+            report(updates, ids, not_sent(&e));
+            None
+        }
+    }
+}
+
+/// Returns the update of a transaction that could not be sent.
+fn not_sent<S>(e: &AccountError<S>) -> TxUpdate
+where
+    AccountError<S>: std::fmt::Display,
+{
+    match revert_reason(e) {
+        Some(reason) => TxUpdate::Reverted {
+            transaction_hash: None,
+            reason,
+            receipt: None,
+        },
+        None => TxUpdate::Failed {
+            transaction_hash: None,
+            error: e.to_string(),
+        },
+    }
+}
+
+/// Polls the receipt of the transaction until it is available.
+///
+/// Provider errors are retried, since the node may not have indexed
+/// the transaction yet. The last error is returned on timeout.
+pub(crate) async fn wait_for_receipt<P>(
+    provider: &P,
+    transaction_hash: Felt,
+    config: TxConfig,
+) -> Result<TransactionReceipt, String>
+where
+    P: Provider + Sync,
+{
+    let started_at = Instant::now();
+
+    loop {
+        let last_error = match provider.get_transaction_receipt(transaction_hash).await {
+            Ok(receipt) => return Ok(receipt.receipt),
+            Err(ProviderError::StarknetError(StarknetError::TransactionHashNotFound)) => {
+                "transaction not found".to_string()
+            }
+            Err(e) => e.to_string(),
+        };
+
+        if started_at.elapsed() >= config.receipt_timeout {
+            return Err(format!(
+                "No receipt after {:?} ({})",
+                config.receipt_timeout, last_error
+            ));
+        }
+
+        tokio::time::sleep(config.receipt_poll_interval).await;
+    }
+}
+
+/// Extracts the revert reason from an account error, if the error
+/// is due to the execution of the transaction.
+fn revert_reason<S>(e: &AccountError<S>) -> Option<String> {
+    match e {
+        AccountError::Provider(ProviderError::StarknetError(
+            StarknetError::TransactionExecutionError(data),
+        )) => Some(format!("{:?}", data.execution_error)),
+        AccountError::Provider(ProviderError::StarknetError(StarknetError::ContractError(
+            data,
+        ))) => Some(format!("{:?}", data.revert_error)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimate() -> FeeEstimate {
+        serde_json::from_value(serde_json::json!({
+            "l1_gas_consumed": "0x64",
+            "l1_gas_price": "0xa",
+            "l2_gas_consumed": "0xc8",
+            "l2_gas_price": "0x14",
+            "l1_data_gas_consumed": "0x12c",
+            "l1_data_gas_price": "0x1e",
+            "overall_fee": "0x4e20",
+            "unit": "FRI"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn with_estimate_default_multipliers() {
+        let fee = TxFeeOptions::default().with_estimate(&estimate());
+
+        assert_eq!(fee.l1_gas, Some(150));
+        assert_eq!(fee.l1_gas_price, Some(15));
+        assert_eq!(fee.l2_gas, Some(300));
+        assert_eq!(fee.l2_gas_price, Some(30));
+        assert_eq!(fee.l1_data_gas, Some(450));
+        assert_eq!(fee.l1_data_gas_price, Some(45));
+    }
+
+    #[test]
+    fn with_estimate_keeps_set_bounds() {
+        let fee = TxFeeOptions {
+            l2_gas: Some(1_000),
+            l2_gas_price: Some(1),
+            tip: Some(5),
+            gas_estimate_multiplier: Some(2.0),
+            gas_price_estimate_multiplier: Some(1.0),
+            ..Default::default()
+        }
+        .with_estimate(&estimate());
+
+        assert_eq!(fee.l1_gas, Some(200));
+        assert_eq!(fee.l1_gas_price, Some(10));
+        assert_eq!(fee.l2_gas, Some(1_000));
+        assert_eq!(fee.l2_gas_price, Some(1));
+        assert_eq!(fee.l1_data_gas, Some(600));
+        assert_eq!(fee.l1_data_gas_price, Some(30));
+        assert_eq!(fee.tip, Some(5));
+    }
+
+    fn call(selector: Felt) -> Call {
+        Call {
+            to: Felt::ONE,
+            selector,
+            calldata: vec![],
+        }
+    }
+
+    #[test]
+    fn batch_is_due() {
+        let mut batch = TxBatch::default();
+        assert!(!batch.is_due(TxBatching::Frame));
+        assert!(!batch.is_due(TxBatching::Window(Duration::ZERO)));
+
+        batch.push(DojoTxId(0), vec![call(Felt::ONE)]);
+        assert!(batch.is_due(TxBatching::Disabled));
+        assert!(batch.is_due(TxBatching::Frame));
+        assert!(batch.is_due(TxBatching::Window(Duration::ZERO)));
+        assert!(!batch.is_due(TxBatching::Window(Duration::from_secs(60))));
+    }
+
+    #[test]
+    fn batch_take() {
+        let mut batch = TxBatch::default();
+        batch.push(DojoTxId(0), vec![call(Felt::ONE)]);
+        batch.push(DojoTxId(1), vec![call(Felt::TWO), call(Felt::THREE)]);
+        assert_eq!(batch.len(), 2);
+
+        let (ids, calls) = batch.take();
+        assert_eq!(ids, [DojoTxId(0), DojoTxId(1)]);
+        let selectors: Vec<_> = calls.iter().map(|c| c.selector).collect();
+        assert_eq!(selectors, [Felt::ONE, Felt::TWO, Felt::THREE]);
+
+        assert_eq!(batch.len(), 0);
+        assert!(!batch.is_due(TxBatching::Frame));
+    }
+
+    async fn fetched(nonce: u64) -> Result<Felt, ()> {
+        Ok(Felt::from(nonce))
+    }
+
+    async fn not_fetched() -> Result<Felt, ()> {
+        panic!("the nonce should not be fetched")
+    }
+
+    #[tokio::test]
+    async fn nonces_sequential() {
+        let nonces = NonceTracker::default();
+
+        assert_eq!(nonces.take(fetched(5)).await, Ok(Felt::from(5_u64)));
+        assert_eq!(nonces.take(not_fetched()).await, Ok(Felt::from(6_u64)));
+        assert_eq!(nonces.take(not_fetched()).await, Ok(Felt::from(7_u64)));
+    }
+
+    #[tokio::test]
+    async fn nonces_fetch_failure() {
+        let nonces = NonceTracker::default();
+
+        assert_eq!(nonces.take(async { Err(()) }).await, Err(()));
+        assert_eq!(nonces.take(fetched(5)).await, Ok(Felt::from(5_u64)));
+    }
+
+    #[tokio::test]
+    async fn nonces_resync_after_failed_send() {
+        let nonces = NonceTracker::default();
+        let nonce = nonces.take(fetched(5)).await.unwrap();
+
+        // A failed estimate or send, before any later nonce is handed out.
+        nonces.resync(nonce).await;
+        assert_eq!(nonces.take(fetched(5)).await, Ok(Felt::from(5_u64)));
+    }
+
+    #[tokio::test]
+    async fn nonces_resync_after_receipt_timeout() {
+        let nonces = NonceTracker::default();
+        let first = nonces.take(fetched(5)).await.unwrap();
+        let second = nonces.take(not_fetched()).await.unwrap();
+
+        // The nonce of the second transaction is not handed out again.
+        nonces.resync(first).await;
+        assert_eq!(*nonces.0.lock().await, Some(Felt::from(7_u64)));
+
+        // Both transactions failed, the nonce is fetched again.
+        nonces.resync(second).await;
+        assert_eq!(nonces.take(fetched(5)).await, Ok(Felt::from(5_u64)));
+    }
+
+    #[tokio::test]
+    async fn turns_follow_queue_order() {
+        let mut turns = TxTurns::default();
+        let order = Arc::new(std::sync::Mutex::new(vec![]));
+        let queued: Vec<_> = (0..3).map(|i| (i, turns.turn())).collect();
+
+        // Spawned in reverse order, the tasks still take their turn in the queue order.
+        let tasks: Vec<_> = queued
+            .into_iter()
+            .rev()
+            .map(|(i, mut turn)| {
+                let order = order.clone();
+                tokio::spawn(async move {
+                    turn.wait().await;
+                    tokio::task::yield_now().await;
+                    order.lock().unwrap().push(i);
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(*order.lock().unwrap(), [0, 1, 2]);
+    }
+}